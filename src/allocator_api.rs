@@ -0,0 +1,73 @@
+impl_allocator!(core::alloc::Allocator, core::alloc::AllocError);
+
+#[cfg(test)]
+mod tests {
+    use crate::Alloc;
+    use core::alloc::{AllocError, Allocator, Layout};
+    use std::boxed::Box;
+    use std::vec::Vec;
+
+    #[test]
+    fn vec_new_in() {
+        let mut heap: [u8; 64] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let mut v = Vec::new_in(&alloc);
+        v.extend([1u32, 2, 3, 4]);
+        assert_eq!(v, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn vec_grows_last_in_place() {
+        let mut heap: [u8; 64] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let mut v: Vec<u8, _> = Vec::with_capacity_in(4, &alloc);
+        v.extend([1, 2, 3, 4]);
+        let ptr = v.as_ptr();
+        v.reserve_exact(60);
+        assert_eq!(v.as_ptr(), ptr);
+        v.extend(5..=64);
+        assert_eq!(v.len(), 64);
+        assert!(v.try_reserve_exact(1).is_err());
+    }
+
+    #[test]
+    fn vec_shrink_releases_last() {
+        let mut heap: [u8; 64] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let mut v: Vec<u8, _> = Vec::with_capacity_in(64, &alloc);
+        v.push(1);
+        v.shrink_to_fit();
+        assert_eq!(v.capacity(), 1);
+        let other: Vec<u8, _> = Vec::with_capacity_in(63, &alloc);
+        assert_eq!(other.capacity(), 63);
+    }
+
+    #[test]
+    fn vec_grows_by_copy_when_not_last() {
+        let mut heap: [u8; 64] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let mut v: Vec<u16, _> = Vec::with_capacity_in(2, &alloc);
+        v.extend([1, 2]);
+        let boxed = Box::new_in(7u8, &alloc);
+        v.push(3);
+        assert_eq!(v, [1, 2, 3]);
+        assert_eq!(*boxed, 7);
+    }
+
+    #[test]
+    fn box_new_in() {
+        let mut heap: [u8; 16] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let boxed = Box::new_in(0x1122_3344_5566_7788u64, &alloc);
+        assert_eq!(*boxed, 0x1122_3344_5566_7788);
+        assert_eq!(Box::try_new_in(1u128, &alloc).err(), Some(AllocError));
+    }
+
+    #[test]
+    fn allocate_zeroed() {
+        let mut heap: [u8; 16] = core::array::from_fn(|_| 0xFF);
+        let alloc = Alloc::new(&mut heap);
+        let ptr = (&alloc).allocate_zeroed(Layout::new::<[u8; 8]>()).unwrap();
+        assert_eq!(unsafe { ptr.as_ref() }, &[0; 8]);
+    }
+}
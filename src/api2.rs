@@ -0,0 +1,2 @@
+// Stable counterpart of the `allocator_api` impl
+impl_allocator!(allocator_api2::alloc::Allocator, allocator_api2::alloc::AllocError);
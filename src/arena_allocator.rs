@@ -0,0 +1,193 @@
+use core::alloc::Layout;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+
+use crate::{fill_slice, Alloc, AllocResult, ByteCounter, HeapWriter, OutOfMemory};
+
+/// Common interface of the arena allocators. Implementors only provide
+/// `alloc_layout`; the typed API is built on top of it, with references
+/// borrowing the allocator.
+///
+/// # Safety
+///
+/// `alloc_layout` must return memory that fits `layout`, does not overlap
+/// any other live allocation and stays valid for as long as the allocator
+/// is borrowed.
+#[allow(clippy::mut_from_ref)]
+pub unsafe trait ArenaAllocator {
+    fn alloc_layout(&self, layout: Layout) -> AllocResult<NonNull<[u8]>>;
+
+    fn alloc_zeroed_layout(&self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+        let ptr = self.alloc_layout(layout)?;
+        unsafe { ptr.cast::<u8>().write_bytes(0, layout.size()) };
+        Ok(ptr)
+    }
+
+    fn alloc<T>(&self, item: T) -> AllocResult<&mut T> {
+        let item_ref = self.alloc_uninit::<T>()?;
+        Ok(item_ref.write(item))
+    }
+
+    fn alloc_uninit<T>(&self) -> AllocResult<&mut MaybeUninit<T>> {
+        let ptr = self.alloc_layout(Layout::new::<T>())?;
+        Ok(unsafe { &mut *ptr.as_ptr().cast() })
+    }
+
+    fn alloc_uninit_slice<T>(&self, size: usize) -> AllocResult<&mut [MaybeUninit<T>]> {
+        let layout = Layout::array::<T>(size).map_err(|_| OutOfMemory)?;
+        let arr_ptr = self.alloc_layout(layout)?.as_ptr().cast();
+        Ok(unsafe { core::slice::from_raw_parts_mut(arr_ptr, size) })
+    }
+
+    fn alloc_from_fn<T>(&self, size: usize, mut f: impl FnMut(usize) -> T) -> AllocResult<&mut [T]> {
+        self.try_alloc_from_fn(size, |i| Ok(f(i)))
+    }
+
+    // Note: on error every element already produced by `f` is dropped
+    fn try_alloc_from_fn<T, E>(&self, size: usize, f: impl FnMut(usize) -> Result<T, E>) -> Result<&mut [T], E>
+        where E: From<OutOfMemory>
+    {
+        let arr_ptr = self.alloc_uninit_slice::<T>(size)?.as_mut_ptr().cast::<T>();
+        unsafe { fill_slice(arr_ptr, size, f) }
+    }
+
+    fn alloc_slice_copy<T: Copy>(&self, src: &[T]) -> AllocResult<&mut [T]> {
+        let arr_ptr = self.alloc_uninit_slice::<T>(src.len())?.as_mut_ptr().cast::<T>();
+        unsafe {
+            core::ptr::copy_nonoverlapping(src.as_ptr(), arr_ptr, src.len());
+            Ok(core::slice::from_raw_parts_mut(arr_ptr, src.len()))
+        }
+    }
+
+    fn alloc_slice_clone<T: Clone>(&self, src: &[T]) -> AllocResult<&mut [T]> {
+        self.alloc_from_fn(src.len(), |i| src[i].clone())
+    }
+
+    // Note: panics if the iterator yields fewer items than its `len()`
+    fn alloc_slice_fill_iter<T, I>(&self, iter: I) -> AllocResult<&mut [T]>
+        where I: IntoIterator<Item = T>, I::IntoIter: ExactSizeIterator
+    {
+        let mut iter = iter.into_iter();
+        self.alloc_from_fn(iter.len(), |_| iter.next().expect("iterator shorter than its len()"))
+    }
+
+    fn alloc_str(&self, src: &str) -> AllocResult<&mut str> {
+        let bytes = self.alloc_slice_copy(src.as_bytes())?;
+        Ok(unsafe { core::str::from_utf8_unchecked_mut(bytes) })
+    }
+
+    // Formats twice: once to measure the output, once into the allocation
+    fn alloc_fmt(&self, args: fmt::Arguments<'_>) -> AllocResult<&mut str> {
+        let mut counter = ByteCounter(0);
+        if fmt::write(&mut counter, args).is_err() {
+            panic!("a formatting trait implementation returned an error");
+        }
+
+        let bytes = self.alloc_uninit_slice::<u8>(counter.0)?;
+        let mut writer = HeapWriter { ptr: bytes.as_mut_ptr().cast(), capacity: bytes.len(), len: 0, overflow: false };
+        if fmt::write(&mut writer, args).is_err() {
+            panic!("a formatting trait implementation returned an error");
+        }
+        Ok(unsafe { core::str::from_utf8_unchecked_mut(core::slice::from_raw_parts_mut(writer.ptr, writer.len)) })
+    }
+}
+
+unsafe impl<A: ArenaAllocator + ?Sized> ArenaAllocator for &A {
+    fn alloc_layout(&self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+        (**self).alloc_layout(layout)
+    }
+}
+
+unsafe impl ArenaAllocator for Alloc<'_> {
+    fn alloc_layout(&self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+        Alloc::alloc_layout(self, layout)
+    }
+
+    // Formats once, straight into the free space
+    fn alloc_fmt(&self, args: fmt::Arguments<'_>) -> AllocResult<&mut str> {
+        self.format(args)
+    }
+}
+
+#[cfg(feature = "alloc")]
+unsafe impl ArenaAllocator for crate::ChunkedAlloc {
+    fn alloc_layout(&self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+        crate::ChunkedAlloc::alloc_layout(self, layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Heap;
+    use core::cell::Cell;
+    use std::string::String;
+
+    // Custom backend: counts calls and forwards to an `Alloc`
+    struct Tracing<'a> {
+        inner: Alloc<'a>,
+        calls: Cell<usize>,
+    }
+
+    unsafe impl ArenaAllocator for Tracing<'_> {
+        fn alloc_layout(&self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+            self.calls.set(self.calls.get() + 1);
+            self.inner.alloc_layout(layout)
+        }
+    }
+
+    #[test]
+    fn typed_api_over_alloc_layout() {
+        let mut heap = Heap([0u8; 128]);
+        let alloc = Tracing { inner: Alloc::new(&mut heap.0), calls: Cell::new(0) };
+        let value = ArenaAllocator::alloc(&alloc, 7u64).unwrap();
+        let squares = alloc.alloc_from_fn(4, |i| i * i).unwrap();
+        let copied = alloc.alloc_slice_copy(&[1u16, 2, 3]).unwrap();
+        let filled = alloc.alloc_slice_fill_iter(b"xyz".iter().copied()).unwrap();
+        let s = alloc.alloc_str("tracing").unwrap();
+        let zeroed = alloc.alloc_zeroed_layout(Layout::new::<u32>()).unwrap();
+        assert_eq!(*value, 7);
+        assert_eq!(squares, &[0, 1, 4, 9]);
+        assert_eq!(copied, &[1, 2, 3]);
+        assert_eq!(filled, b"xyz");
+        assert_eq!(s, "tracing");
+        assert_eq!(unsafe { zeroed.as_ref() }, &[0; 4]);
+        assert_eq!(alloc.calls.get(), 6);
+        assert_eq!(ArenaAllocator::alloc(&alloc, [0u8; 128]), Err(OutOfMemory));
+    }
+
+    #[test]
+    fn clone_and_fmt() {
+        let mut heap = Heap([0u8; 256]);
+        let alloc = Tracing { inner: Alloc::new(&mut heap.0), calls: Cell::new(0) };
+        let src = [String::from("a"), String::from("b")];
+        let cloned = ArenaAllocator::alloc_slice_clone(&alloc, &src).unwrap();
+        assert_eq!(cloned, &src);
+        unsafe { core::ptr::drop_in_place(cloned) };
+        let formatted = ArenaAllocator::alloc_fmt(&alloc, format_args!("{}:{:04}", "id", 42)).unwrap();
+        assert_eq!(formatted, "id:0042");
+        assert_eq!(ArenaAllocator::alloc_fmt(&alloc, format_args!("{:>300}", 0)), Err(OutOfMemory));
+    }
+
+    #[test]
+    fn alloc_fmt_through_trait_matches_inherent() {
+        let mut heap = Heap([0u8; 16]);
+        let alloc = Alloc::new(&mut heap.0);
+        assert_eq!(ArenaAllocator::alloc_fmt(&alloc, format_args!("{}", 12345)).unwrap(), "12345");
+        assert_eq!(ArenaAllocator::alloc_fmt(&alloc, format_args!("{:>16}", 0)), Err(OutOfMemory));
+        assert_eq!(alloc.used(), 5);
+        assert_eq!(alloc.alloc_fmt(format_args!("{:>11}", 0)).unwrap().len(), 11);
+    }
+
+    #[test]
+    fn try_alloc_from_fn_error() {
+        let mut heap = Heap([0u8; 64]);
+        let alloc = Tracing { inner: Alloc::new(&mut heap.0), calls: Cell::new(0) };
+        let result: Result<&mut [u8], OutOfMemory> =
+            alloc.try_alloc_from_fn(4, |i| if i < 2 { Ok(i as u8) } else { Err(OutOfMemory) });
+        assert_eq!(result, Err(OutOfMemory));
+        let uninit = alloc.alloc_uninit_slice::<u32>(2).unwrap();
+        assert_eq!(uninit.len(), 2);
+    }
+}
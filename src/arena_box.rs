@@ -0,0 +1,167 @@
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+
+/// Owning pointer into an arena: dropping it runs `T`'s destructor,
+/// but the memory itself is only reclaimed with the arena.
+pub struct ArenaBox<'a, T: ?Sized> {
+    ptr: NonNull<T>,
+    _owns: PhantomData<T>,
+    _mem: PhantomData<&'a mut [u8]>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for ArenaBox<'_, T> {}
+unsafe impl<T: ?Sized + Sync> Sync for ArenaBox<'_, T> {}
+
+impl<'a, T: ?Sized> ArenaBox<'a, T> {
+    // Safety: `item` must be initialized, owned by nobody else and never dropped elsewhere
+    pub(crate) unsafe fn from_mut(item: &'a mut T) -> Self {
+        ArenaBox { ptr: NonNull::from(item), _owns: PhantomData, _mem: PhantomData }
+    }
+
+    /// Gives up ownership without running the destructor.
+    pub fn leak(this: Self) -> &'a mut T {
+        let this = ManuallyDrop::new(this);
+        unsafe { &mut *this.ptr.as_ptr() }
+    }
+}
+
+impl<T> ArenaBox<'_, T> {
+    /// Moves the value out of the arena.
+    pub fn into_inner(this: Self) -> T {
+        let this = ManuallyDrop::new(this);
+        unsafe { this.ptr.as_ptr().read() }
+    }
+}
+
+impl<T: ?Sized> Drop for ArenaBox<'_, T> {
+    fn drop(&mut self) {
+        unsafe { core::ptr::drop_in_place(self.ptr.as_ptr()) }
+    }
+}
+
+impl<T: ?Sized> Deref for ArenaBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for ArenaBox<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T: ?Sized> AsRef<T> for ArenaBox<'_, T> {
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+impl<T: ?Sized> AsMut<T> for ArenaBox<'_, T> {
+    fn as_mut(&mut self) -> &mut T {
+        self
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for ArenaBox<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for ArenaBox<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T: ?Sized + PartialEq> PartialEq for ArenaBox<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: ?Sized + Eq> Eq for ArenaBox<'_, T> {}
+
+impl<T: ?Sized + Hash> Hash for ArenaBox<'_, T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::DropCounter;
+    use crate::Alloc;
+    use std::cell::Cell;
+    use std::collections::hash_map::DefaultHasher;
+    use std::format;
+    use std::string::String;
+
+    #[test]
+    fn drop_runs_destructor() {
+        let drops = Cell::new(0);
+        let mut heap: [u8; 64] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let boxed = alloc.alloc_box(DropCounter(&drops)).unwrap();
+        assert_eq!(drops.get(), 0);
+        drop(boxed);
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn into_inner_and_leak() {
+        let drops = Cell::new(0);
+        let mut heap: [u8; 64] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let counter = ArenaBox::into_inner(alloc.alloc_box(DropCounter(&drops)).unwrap());
+        assert_eq!(drops.get(), 0);
+        drop(counter);
+        assert_eq!(drops.get(), 1);
+
+        let leaked = ArenaBox::leak(alloc.alloc_box(DropCounter(&drops)).unwrap());
+        assert!(core::ptr::eq(leaked.0, &drops));
+        assert_eq!(drops.get(), 1);
+    }
+
+    #[test]
+    fn deref_and_forwarding() {
+        let mut heap: [u8; 256] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let mut boxed = alloc.alloc_box(String::from("arena")).unwrap();
+        boxed.push_str(" box");
+        assert_eq!(format!("{boxed}"), "arena box");
+        assert_eq!(format!("{boxed:?}"), "\"arena box\"");
+        assert_eq!(boxed, alloc.alloc_box(String::from("arena box")).unwrap());
+
+        let hash = |value: &dyn Fn(&mut DefaultHasher)| {
+            let mut hasher = DefaultHasher::new();
+            value(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&|h| boxed.hash(h)), hash(&|h| "arena box".hash(h)));
+    }
+
+    #[test]
+    fn slice_box_drops_every_element() {
+        let drops = Cell::new(0);
+        let mut heap: [u8; 64] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let boxed = alloc.alloc_box_from_fn(3, |_| DropCounter(&drops)).unwrap();
+        assert_eq!(boxed.len(), 3);
+        drop(boxed);
+        assert_eq!(drops.get(), 3);
+
+        let mut numbers = alloc.alloc_box_from_fn(4, |i| i as u16).unwrap();
+        numbers.reverse();
+        assert_eq!(&*numbers, &[3, 2, 1, 0]);
+        assert_eq!(format!("{numbers:?}"), "[3, 2, 1, 0]");
+    }
+}
@@ -0,0 +1,246 @@
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::ops::{Deref, DerefMut};
+
+use crate::{Alloc, AllocResult, ArenaVec, OutOfMemory};
+
+/// Growable UTF-8 string in an `Alloc` heap, grown like `ArenaVec`.
+pub struct ArenaString<'a> {
+    vec: ArenaVec<'a, u8>,
+}
+
+impl<'a> ArenaString<'a> {
+    pub fn new_in(alloc: &'a Alloc<'a>) -> Self {
+        ArenaString { vec: ArenaVec::new_in(alloc) }
+    }
+
+    pub fn with_capacity_in(capacity: usize, alloc: &'a Alloc<'a>) -> AllocResult<Self> {
+        Ok(ArenaString { vec: ArenaVec::with_capacity_in(capacity, alloc)? })
+    }
+
+    pub fn from_str_in(src: &str, alloc: &'a Alloc<'a>) -> AllocResult<Self> {
+        let mut string = Self::with_capacity_in(src.len(), alloc)?;
+        string.push_str(src)?;
+        Ok(string)
+    }
+
+    // Used by `arena_format!`. Panics if a formatting trait implementation fails
+    pub fn from_fmt_in(args: fmt::Arguments<'_>, alloc: &'a Alloc<'a>) -> AllocResult<Self> {
+        struct Writer<'s, 'a> {
+            string: &'s mut ArenaString<'a>,
+            out_of_memory: bool,
+        }
+
+        impl fmt::Write for Writer<'_, '_> {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                self.out_of_memory = self.string.push_str(s).is_err();
+                if self.out_of_memory { Err(fmt::Error) } else { Ok(()) }
+            }
+        }
+
+        let mut string = Self::new_in(alloc);
+        let mut writer = Writer { string: &mut string, out_of_memory: false };
+        if fmt::write(&mut writer, args).is_err() {
+            if writer.out_of_memory { return Err(OutOfMemory); }
+            panic!("a formatting trait implementation returned an error");
+        }
+        Ok(string)
+    }
+
+    pub fn len(&self) -> usize {
+        self.vec.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.vec.capacity()
+    }
+
+    pub fn as_str(&self) -> &str {
+        unsafe { core::str::from_utf8_unchecked(&self.vec) }
+    }
+
+    pub fn as_mut_str(&mut self) -> &mut str {
+        unsafe { core::str::from_utf8_unchecked_mut(&mut self.vec) }
+    }
+
+    pub fn reserve(&mut self, additional: usize) -> AllocResult<()> {
+        self.vec.reserve(additional)
+    }
+
+    pub fn push_str(&mut self, s: &str) -> AllocResult<()> {
+        self.vec.extend_from_slice_copy(s.as_bytes())
+    }
+
+    pub fn push(&mut self, ch: char) -> AllocResult<()> {
+        self.push_str(ch.encode_utf8(&mut [0; 4]))
+    }
+
+    pub fn pop(&mut self) -> Option<char> {
+        let ch = self.as_str().chars().next_back()?;
+        self.vec.truncate(self.len() - ch.len_utf8());
+        Some(ch)
+    }
+
+    // Note: panics if `len` is not on a char boundary
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len() { return; }
+
+        assert!(self.as_str().is_char_boundary(len), "truncate length {len} is not a char boundary");
+        self.vec.truncate(len);
+    }
+
+    pub fn clear(&mut self) {
+        self.vec.clear();
+    }
+
+    pub fn into_bump_str(self) -> &'a mut str {
+        unsafe { core::str::from_utf8_unchecked_mut(self.vec.into_bump_slice()) }
+    }
+}
+
+impl Deref for ArenaString<'_> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl DerefMut for ArenaString<'_> {
+    fn deref_mut(&mut self) -> &mut str {
+        self.as_mut_str()
+    }
+}
+
+impl fmt::Write for ArenaString<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s).map_err(|OutOfMemory| fmt::Error)
+    }
+}
+
+impl fmt::Display for ArenaString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Debug for ArenaString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl PartialEq for ArenaString<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for ArenaString<'_> {}
+
+impl PartialEq<str> for ArenaString<'_> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for ArenaString<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl Hash for ArenaString<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+/// Formats into a new `ArenaString`, returning `AllocResult<ArenaString>`.
+///
+/// ```
+/// use simple_allocator::{arena_format, Alloc};
+///
+/// let mut heap = [0u8; 32];
+/// let alloc = Alloc::new(&mut heap);
+/// let greeting = arena_format!(&alloc, "hello, {}!", "arena").unwrap();
+/// assert_eq!(greeting, "hello, arena!");
+/// ```
+#[macro_export]
+macro_rules! arena_format {
+    ($alloc:expr, $($arg:tt)*) => {
+        $crate::ArenaString::from_fmt_in(::core::format_args!($($arg)*), $alloc)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+
+    #[test]
+    fn push_grows_in_place_at_top() {
+        let mut heap: [u8; 16] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let mut s = ArenaString::new_in(&alloc);
+        s.push_str("abc").unwrap();
+        let ptr = s.as_ptr();
+        s.push('é').unwrap();
+        s.push_str("0123456789").unwrap();
+        assert_eq!(s.as_ptr(), ptr);
+        assert_eq!(s, "abcé0123456789");
+        assert_eq!(s.push_str("xyz"), Err(OutOfMemory));
+        assert_eq!(s.len(), 15);
+    }
+
+    #[test]
+    fn pop_and_truncate() {
+        let mut heap: [u8; 32] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let mut s = ArenaString::from_str_in("añb", &alloc).unwrap();
+        assert_eq!(s.pop(), Some('b'));
+        assert_eq!(s.pop(), Some('ñ'));
+        s.push_str("bc").unwrap();
+        s.truncate(2);
+        assert_eq!(s, "ab");
+        s.clear();
+        assert!(s.is_empty());
+        assert_eq!(s.pop(), None);
+    }
+
+    #[test]
+    fn fmt_write() {
+        let mut heap: [u8; 32] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let mut s = ArenaString::new_in(&alloc);
+        write!(s, "{}+{}={:03}", 1, 2, 3).unwrap();
+        assert_eq!(s, "1+2=003");
+        assert!(write!(s, "{:>32}", 0).is_err());
+    }
+
+    #[test]
+    fn arena_format() {
+        let mut heap: [u8; 16] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let s = arena_format!(&alloc, "{}-{}", "id", 42).unwrap();
+        assert_eq!(s.into_bump_str(), "id-42");
+        assert_eq!(arena_format!(&alloc, "{:>12}", 0), Err(OutOfMemory));
+    }
+
+    #[test]
+    fn into_bump_str_outlives_string() {
+        let mut heap: [u8; 16] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let s = {
+            let mut s = ArenaString::new_in(&alloc);
+            s.push_str("bump").unwrap();
+            s.into_bump_str()
+        };
+        s.make_ascii_uppercase();
+        assert_eq!(s, "BUMP");
+    }
+}
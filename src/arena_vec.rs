@@ -0,0 +1,426 @@
+use core::alloc::Layout;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+
+use crate::{Alloc, AllocResult, OutOfMemory};
+
+const MIN_CAPACITY: usize = 4;
+
+/// Growable vector in an `Alloc` heap. It grows in place while it is the
+/// last allocation and is copied to a fresh region otherwise; the old
+/// region is not reclaimed.
+pub struct ArenaVec<'a, T> {
+    alloc: &'a Alloc<'a>,
+    ptr: NonNull<T>,
+    len: usize,
+    cap: usize,
+    _owns: PhantomData<T>,
+}
+
+impl<'a, T> ArenaVec<'a, T> {
+    pub fn new_in(alloc: &'a Alloc<'a>) -> Self {
+        let cap = if core::mem::size_of::<T>() == 0 { usize::MAX } else { 0 };
+        ArenaVec { alloc, ptr: NonNull::dangling(), len: 0, cap, _owns: PhantomData }
+    }
+
+    pub fn with_capacity_in(capacity: usize, alloc: &'a Alloc<'a>) -> AllocResult<Self> {
+        let mut vec = Self::new_in(alloc);
+        vec.reserve_exact(capacity)?;
+        Ok(vec)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { core::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { core::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+
+    pub fn reserve(&mut self, additional: usize) -> AllocResult<()> {
+        let required = self.len.checked_add(additional).ok_or(OutOfMemory)?;
+        if required <= self.cap { return Ok(()); }
+
+        let amortized = required.max(self.cap.saturating_mul(2)).max(MIN_CAPACITY);
+        self.grow_to(amortized).or_else(|_| self.grow_to(required))
+    }
+
+    pub fn reserve_exact(&mut self, additional: usize) -> AllocResult<()> {
+        let required = self.len.checked_add(additional).ok_or(OutOfMemory)?;
+        if required <= self.cap { return Ok(()); }
+
+        self.grow_to(required)
+    }
+
+    // Extends in place when this is the last allocation, relocates otherwise
+    fn grow_to(&mut self, new_cap: usize) -> AllocResult<()> {
+        let new_layout = Layout::array::<T>(new_cap).map_err(|_| OutOfMemory)?;
+        let old_size = self.cap * core::mem::size_of::<T>();
+        if self.cap > 0 && self.alloc.resize_last(self.ptr.cast(), old_size, new_layout.size()) {
+            self.cap = new_cap;
+            return Ok(());
+        }
+
+        let new_ptr = self.alloc.reserve(new_layout)?.cast::<T>();
+        unsafe { ptr::copy_nonoverlapping(self.ptr.as_ptr(), new_ptr.as_ptr(), self.len) };
+        self.ptr = new_ptr;
+        self.cap = new_cap;
+        Ok(())
+    }
+
+    // Note: on error `value` is dropped
+    pub fn push(&mut self, value: T) -> AllocResult<()> {
+        if self.len == self.cap { self.reserve(1)?; }
+
+        unsafe { self.ptr.as_ptr().add(self.len).write(value) };
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 { return None; }
+
+        self.len -= 1;
+        Some(unsafe { self.ptr.as_ptr().add(self.len).read() })
+    }
+
+    // Note: panics if `index > len`, on error `value` is dropped
+    pub fn insert(&mut self, index: usize, value: T) -> AllocResult<()> {
+        assert!(index <= self.len, "insertion index {index} out of bounds for length {}", self.len);
+        if self.len == self.cap { self.reserve(1)?; }
+
+        unsafe {
+            let item_ptr = self.ptr.as_ptr().add(index);
+            ptr::copy(item_ptr, item_ptr.add(1), self.len - index);
+            item_ptr.write(value);
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    // Note: panics if `index >= len`
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(index < self.len, "removal index {index} out of bounds for length {}", self.len);
+
+        unsafe {
+            let item_ptr = self.ptr.as_ptr().add(index);
+            let value = item_ptr.read();
+            ptr::copy(item_ptr.add(1), item_ptr, self.len - index - 1);
+            self.len -= 1;
+            value
+        }
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len { return; }
+
+        let tail = ptr::slice_from_raw_parts_mut(unsafe { self.ptr.as_ptr().add(len) }, self.len - len);
+        self.len = len;
+        unsafe { ptr::drop_in_place(tail) };
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    pub fn try_extend(&mut self, iter: impl IntoIterator<Item = T>) -> AllocResult<()> {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0)?;
+        for value in iter {
+            self.push(value)?;
+        }
+        Ok(())
+    }
+
+    pub fn extend_from_slice(&mut self, src: &[T]) -> AllocResult<()>
+        where T: Clone
+    {
+        self.try_extend(src.iter().cloned())
+    }
+
+    // Reserves once and copies the whole slice
+    pub fn extend_from_slice_copy(&mut self, src: &[T]) -> AllocResult<()>
+        where T: Copy
+    {
+        self.reserve(src.len())?;
+        unsafe { ptr::copy_nonoverlapping(src.as_ptr(), self.ptr.as_ptr().add(self.len), src.len()) };
+        self.len += src.len();
+        Ok(())
+    }
+
+    /// Gives up ownership of the elements, which are no longer dropped.
+    pub fn into_bump_slice(self) -> &'a mut [T] {
+        let this = ManuallyDrop::new(self);
+        unsafe { core::slice::from_raw_parts_mut(this.ptr.as_ptr(), this.len) }
+    }
+}
+
+impl<T> Drop for ArenaVec<'_, T> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(self.as_mut_slice()) }
+    }
+}
+
+impl<T> Deref for ArenaVec<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T> DerefMut for ArenaVec<'_, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ArenaVec<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_slice(), f)
+    }
+}
+
+impl<T: PartialEq> PartialEq for ArenaVec<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<T: PartialEq> PartialEq<[T]> for ArenaVec<'_, T> {
+    fn eq(&self, other: &[T]) -> bool {
+        self.as_slice() == other
+    }
+}
+
+impl<T: PartialEq, const N: usize> PartialEq<[T; N]> for ArenaVec<'_, T> {
+    fn eq(&self, other: &[T; N]) -> bool {
+        self.as_slice() == other
+    }
+}
+
+// Note: panics when the heap is out of memory, use `try_extend` to handle it
+impl<T> Extend<T> for ArenaVec<'_, T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.try_extend(iter).expect("arena out of memory")
+    }
+}
+
+impl<'v, T> IntoIterator for &'v ArenaVec<'_, T> {
+    type Item = &'v T;
+    type IntoIter = core::slice::Iter<'v, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'v, T> IntoIterator for &'v mut ArenaVec<'_, T> {
+    type Item = &'v mut T;
+    type IntoIter = core::slice::IterMut<'v, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<'a, T> IntoIterator for ArenaVec<'a, T> {
+    type Item = T;
+    type IntoIter = ArenaVecIntoIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let this = ManuallyDrop::new(self);
+        ArenaVecIntoIter { ptr: this.ptr, start: 0, end: this.len, _mem: PhantomData }
+    }
+}
+
+/// Owning iterator over the elements of an `ArenaVec`.
+pub struct ArenaVecIntoIter<'a, T> {
+    ptr: NonNull<T>,
+    start: usize,
+    end: usize,
+    _mem: PhantomData<(&'a mut [u8], T)>,
+}
+
+impl<T> Iterator for ArenaVecIntoIter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.start == self.end { return None; }
+
+        self.start += 1;
+        Some(unsafe { self.ptr.as_ptr().add(self.start - 1).read() })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.end - self.start, Some(self.end - self.start))
+    }
+}
+
+impl<T> DoubleEndedIterator for ArenaVecIntoIter<'_, T> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.start == self.end { return None; }
+
+        self.end -= 1;
+        Some(unsafe { self.ptr.as_ptr().add(self.end).read() })
+    }
+}
+
+impl<T> ExactSizeIterator for ArenaVecIntoIter<'_, T> {}
+
+impl<T> Drop for ArenaVecIntoIter<'_, T> {
+    fn drop(&mut self) {
+        let rest = ptr::slice_from_raw_parts_mut(unsafe { self.ptr.as_ptr().add(self.start) }, self.end - self.start);
+        unsafe { ptr::drop_in_place(rest) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{DropCounter, Heap};
+    use std::cell::Cell;
+    use std::string::String;
+    use std::vec::Vec;
+
+    #[test]
+    fn push_grows_in_place_at_top() {
+        let mut heap = Heap([0u8; 64]);
+        let alloc = Alloc::new(&mut heap.0);
+        let mut v = ArenaVec::new_in(&alloc);
+        v.push(0u32).unwrap();
+        let ptr = v.as_ptr();
+        for i in 1..16 {
+            v.push(i).unwrap();
+        }
+        assert_eq!(v.as_ptr(), ptr);
+        assert_eq!(v.len(), 16);
+        assert_eq!(v.push(16), Err(OutOfMemory));
+        assert!(v.iter().copied().eq(0..16));
+    }
+
+    #[test]
+    fn extend_from_slice_copy() {
+        let mut heap = Heap([0u8; 32]);
+        let alloc = Alloc::new(&mut heap.0);
+        let mut v = ArenaVec::new_in(&alloc);
+        v.extend_from_slice_copy(&[1u16, 2, 3]).unwrap();
+        v.extend_from_slice_copy(&[4, 5]).unwrap();
+        assert_eq!(v.as_slice(), &[1, 2, 3, 4, 5]);
+        assert_eq!(v.extend_from_slice_copy(&[0; 12]), Err(OutOfMemory));
+        assert_eq!(v.len(), 5);
+    }
+
+    #[test]
+    fn push_relocates_when_not_last() {
+        let mut heap = Heap([0u8; 128]);
+        let alloc = Alloc::new(&mut heap.0);
+        let mut v = ArenaVec::with_capacity_in(2, &alloc).unwrap();
+        v.extend([1u16, 2]);
+        let other = alloc.alloc(7u8).unwrap();
+        let ptr = v.as_ptr();
+        v.push(3).unwrap();
+        assert_ne!(v.as_ptr(), ptr);
+        assert_eq!(v, [1, 2, 3]);
+        assert_eq!(*other, 7);
+    }
+
+    #[test]
+    fn insert_remove_truncate() {
+        let mut heap = Heap([0u8; 256]);
+        let alloc = Alloc::new(&mut heap.0);
+        let mut v = ArenaVec::new_in(&alloc);
+        v.try_extend(0..5u8).unwrap();
+        v.insert(0, 10).unwrap();
+        v.insert(6, 20).unwrap();
+        v.insert(3, 30).unwrap();
+        assert_eq!(v, [10, 0, 1, 30, 2, 3, 4, 20]);
+        assert_eq!(v.remove(3), 30);
+        assert_eq!(v.remove(0), 10);
+        assert_eq!(v.pop(), Some(20));
+        v.truncate(2);
+        assert_eq!(v, [0, 1]);
+        v.clear();
+        assert!(v.is_empty());
+    }
+
+    #[test]
+    fn drops_elements() {
+        let drops = Cell::new(0);
+        let mut heap = Heap([0u8; 256]);
+        let alloc = Alloc::new(&mut heap.0);
+        let mut v = ArenaVec::new_in(&alloc);
+        for _ in 0..6 {
+            v.push(DropCounter(&drops)).unwrap();
+        }
+        v.truncate(4);
+        assert_eq!(drops.get(), 2);
+        drop(v.remove(0));
+        assert_eq!(drops.get(), 3);
+        drop(v);
+        assert_eq!(drops.get(), 6);
+    }
+
+    #[test]
+    fn into_bump_slice_outlives_vec() {
+        let mut heap = Heap([0u8; 64]);
+        let alloc = Alloc::new(&mut heap.0);
+        let slice = {
+            let mut v = ArenaVec::new_in(&alloc);
+            v.extend_from_slice(&[String::from("a"), String::from("b")]).unwrap();
+            v.into_bump_slice()
+        };
+        slice[0].push('!');
+        assert_eq!(slice, ["a!", "b"]);
+        unsafe { ptr::drop_in_place(slice) };
+    }
+
+    #[test]
+    fn into_iter() {
+        let drops = Cell::new(0);
+        let mut heap = Heap([0u8; 128]);
+        let alloc = Alloc::new(&mut heap.0);
+        let mut v = ArenaVec::new_in(&alloc);
+        v.extend((0..4u64).map(|i| i * 10));
+        for x in &mut v {
+            *x += 1;
+        }
+        assert_eq!((&v).into_iter().sum::<u64>(), 64);
+        let mut iter = v.into_iter();
+        assert_eq!(iter.next_back(), Some(31));
+        assert_eq!(iter.collect::<Vec<_>>(), [1, 11, 21]);
+
+        let mut counters = ArenaVec::new_in(&alloc);
+        counters.extend([DropCounter(&drops), DropCounter(&drops), DropCounter(&drops)]);
+        let mut iter = counters.into_iter();
+        drop(iter.next());
+        drop(iter);
+        assert_eq!(drops.get(), 3);
+    }
+
+    #[test]
+    fn zero_sized_elements() {
+        let alloc = Alloc::new(&mut []);
+        let mut v = ArenaVec::new_in(&alloc);
+        for _ in 0..1000 {
+            v.push(()).unwrap();
+        }
+        assert_eq!(v.len(), 1000);
+        assert_eq!(v.capacity(), usize::MAX);
+    }
+}
@@ -0,0 +1,270 @@
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+use crate::{align_padding, AllocResult, ArenaAllocator, OutOfMemory};
+
+// Free blocks hold the offset of the next free block of the same order
+const WORD: usize = core::mem::size_of::<usize>();
+const MAX_ORDERS: usize = usize::BITS as usize;
+const NIL: usize = usize::MAX;
+
+/// Buddy allocator over the largest power-of-two region of the heap that is
+/// aligned to its own size. Blocks of order `k` are `min_block << k` bytes
+/// and aligned to their size; freed blocks merge with their free buddy.
+pub struct BuddyAlloc<'mem> {
+    region: *mut u8,
+    min_block: usize,
+    orders: usize,
+    free: [Cell<usize>; MAX_ORDERS],
+    free_counts: [Cell<usize>; MAX_ORDERS],
+    _mem: PhantomData<&'mem mut [u8]>,
+}
+
+unsafe impl Send for BuddyAlloc<'_> {}
+
+impl<'mem> BuddyAlloc<'mem> {
+    // `min_block` is rounded up to a power of two that can hold a free list link
+    pub fn new(heap: &'mem mut [u8], min_block: usize) -> Self {
+        let min_block = min_block.max(WORD).next_power_of_two();
+        let (offset, region_size) = Self::find_region(heap, min_block);
+        let alloc = BuddyAlloc {
+            region: unsafe { heap.as_mut_ptr().add(offset) },
+            min_block,
+            orders: if region_size == 0 { 0 } else { (region_size / min_block).trailing_zeros() as usize + 1 },
+            free: core::array::from_fn(|_| Cell::new(NIL)),
+            free_counts: core::array::from_fn(|_| Cell::new(0)),
+            _mem: PhantomData,
+        };
+        if alloc.orders > 0 {
+            alloc.push(alloc.orders - 1, 0);
+        }
+        alloc
+    }
+
+    // Offset and size of the largest region aligned to its own size, empty if below `min_block`
+    fn find_region(heap: &[u8], min_block: usize) -> (usize, usize) {
+        let mut size = match heap.len().checked_ilog2() {
+            Some(log) => 1 << log,
+            None => return (0, 0),
+        };
+        while size >= min_block {
+            let padding = align_padding(heap.as_ptr() as usize, size);
+            if padding <= heap.len() - size { return (padding, size); }
+            size /= 2;
+        }
+        (0, 0)
+    }
+
+    pub fn min_block(&self) -> usize {
+        self.min_block
+    }
+
+    /// Number of block orders, zero when the heap cannot hold a single block.
+    pub fn orders(&self) -> usize {
+        self.orders
+    }
+
+    pub fn block_size(&self, order: usize) -> usize {
+        self.min_block << order
+    }
+
+    pub fn region_size(&self) -> usize {
+        if self.orders == 0 { 0 } else { self.block_size(self.orders - 1) }
+    }
+
+    pub fn free_count(&self, order: usize) -> usize {
+        self.free_counts.get(order).map_or(0, Cell::get)
+    }
+
+    pub fn free_bytes(&self) -> usize {
+        (0..self.orders).map(|order| self.free_count(order) * self.block_size(order)).sum()
+    }
+
+    pub fn alloc_layout(&self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+        let order = self.order_of(layout).ok_or(OutOfMemory)?;
+        let mut found = order;
+        while self.free[found].get() == NIL {
+            found += 1;
+            if found == self.orders { return Err(OutOfMemory); }
+        }
+
+        let offset = self.pop(found);
+        while found > order {
+            found -= 1;
+            self.push(found, offset + self.block_size(found));
+        }
+        let ptr = unsafe { NonNull::new_unchecked(self.region.add(offset)) };
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    /// Frees the block at `ptr`, merging it with its buddy while the buddy is free.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must come from this allocator with the same `layout`, not be
+    /// freed yet, and nothing may use the allocation afterwards.
+    pub unsafe fn dealloc(&self, ptr: NonNull<u8>, layout: Layout) {
+        let mut order = self.order_of(layout).expect("layout was never allocated here");
+        let mut offset = ptr.as_ptr() as usize - self.region as usize;
+        while order + 1 < self.orders && self.remove(order, offset ^ self.block_size(order)) {
+            offset &= !self.block_size(order);
+            order += 1;
+        }
+        self.push(order, offset);
+    }
+
+    // Smallest order whose blocks fit `layout`; blocks are aligned to their size
+    fn order_of(&self, layout: Layout) -> Option<usize> {
+        let size = layout.size().max(layout.align()).max(self.min_block).checked_next_power_of_two()?;
+        let order = (size / self.min_block).trailing_zeros() as usize;
+        (order < self.orders).then_some(order)
+    }
+
+    fn push(&self, order: usize, offset: usize) {
+        self.set_next(offset, self.free[order].get());
+        self.free[order].set(offset);
+        self.free_counts[order].set(self.free_counts[order].get() + 1);
+    }
+
+    fn pop(&self, order: usize) -> usize {
+        let offset = self.free[order].get();
+        self.free[order].set(self.next(offset));
+        self.free_counts[order].set(self.free_counts[order].get() - 1);
+        offset
+    }
+
+    // Takes the block at `offset` off the free list of `order`, if it is there
+    fn remove(&self, order: usize, offset: usize) -> bool {
+        let mut prev = NIL;
+        let mut current = self.free[order].get();
+        while current != NIL && current != offset {
+            prev = current;
+            current = self.next(current);
+        }
+        if current == NIL { return false; }
+
+        if prev == NIL {
+            self.free[order].set(self.next(current));
+        } else {
+            self.set_next(prev, self.next(current));
+        }
+        self.free_counts[order].set(self.free_counts[order].get() - 1);
+        true
+    }
+
+    fn next(&self, offset: usize) -> usize {
+        unsafe { self.region.add(offset).cast::<usize>().read() }
+    }
+
+    fn set_next(&self, offset: usize, next: usize) {
+        unsafe { self.region.add(offset).cast::<usize>().write(next) }
+    }
+}
+
+unsafe impl ArenaAllocator for BuddyAlloc<'_> {
+    fn alloc_layout(&self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+        BuddyAlloc::alloc_layout(self, layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{Page, Rng};
+    use std::vec::Vec;
+
+    fn addr(ptr: NonNull<[u8]>) -> usize {
+        ptr.cast::<u8>().as_ptr() as usize
+    }
+
+    #[test]
+    fn takes_largest_aligned_region() {
+        let mut page = Page([0u8; 4096 + 512]);
+        assert_eq!(BuddyAlloc::new(&mut page.0, 16).region_size(), 4096);
+        let alloc = BuddyAlloc::new(&mut page.0[8..], 16);
+        assert_eq!(alloc.region_size(), 2048);
+        assert_eq!(alloc.region as usize % 2048, 0);
+        assert_eq!((alloc.orders(), alloc.free_count(7), alloc.free_bytes()), (8, 1, 2048));
+        assert_eq!(BuddyAlloc::new(&mut page.0[..15], 16).orders(), 0);
+    }
+
+    #[test]
+    fn min_block_is_rounded() {
+        let mut page = Page([0u8; 256]);
+        assert_eq!(BuddyAlloc::new(&mut page.0, 0).min_block(), WORD);
+        assert_eq!(BuddyAlloc::new(&mut page.0, 24).min_block(), 32);
+    }
+
+    #[test]
+    fn split_and_merge() {
+        let mut page = Page([0u8; 256]);
+        let alloc = BuddyAlloc::new(&mut page.0, 32);
+        let layout = Layout::new::<[u8; 20]>();
+        let first = alloc.alloc_layout(layout).unwrap();
+        let counts = |alloc: &BuddyAlloc| (0..alloc.orders()).map(|order| alloc.free_count(order)).collect::<Vec<_>>();
+        assert_eq!(counts(&alloc), [1, 1, 1, 0]);
+
+        let second = alloc.alloc_layout(layout).unwrap();
+        assert_eq!(addr(second) - addr(first), 32);
+        assert_eq!(counts(&alloc), [0, 1, 1, 0]);
+
+        unsafe { alloc.dealloc(first.cast(), layout) };
+        assert_eq!(counts(&alloc), [1, 1, 1, 0]);
+        unsafe { alloc.dealloc(second.cast(), layout) };
+        assert_eq!(counts(&alloc), [0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn blocks_are_aligned() {
+        let mut page = Page([0u8; 1024]);
+        let alloc = BuddyAlloc::new(&mut page.0, 16);
+        alloc.alloc_layout(Layout::new::<u8>()).unwrap();
+        for align in [16, 64, 256] {
+            let ptr = alloc.alloc_layout(Layout::from_size_align(8, align).unwrap()).unwrap();
+            assert!(addr(ptr).is_multiple_of(align));
+        }
+    }
+
+    #[test]
+    fn out_of_memory() {
+        let mut page = Page([0u8; 256]);
+        let alloc = BuddyAlloc::new(&mut page.0, 16);
+        assert_eq!(alloc.alloc_layout(Layout::new::<[u8; 257]>()), Err(OutOfMemory));
+        let whole = alloc.alloc_layout(Layout::new::<[u8; 256]>()).unwrap();
+        assert_eq!(alloc.alloc_layout(Layout::new::<u8>()), Err(OutOfMemory));
+        unsafe { alloc.dealloc(whole.cast(), Layout::new::<[u8; 256]>()) };
+        assert!(alloc.alloc_layout(Layout::new::<u8>()).is_ok());
+    }
+
+    #[test]
+    fn random_workload_merges_back() {
+        let mut page = Page([0u8; 4096]);
+        let alloc = BuddyAlloc::new(&mut page.0, 16);
+        let mut live: Vec<(NonNull<u8>, Layout, u8)> = Vec::new();
+        let mut rng = Rng(0x2545f4914f6cdd1d);
+
+        for step in 0..if cfg!(miri) { 300 } else { 5000 } {
+            if rng.next(2) == 0 && !live.is_empty() {
+                let (ptr, layout, tag) = live.swap_remove(rng.next(live.len()));
+                assert!(unsafe { core::slice::from_raw_parts(ptr.as_ptr(), layout.size()) }.iter().all(|&b| b == tag));
+                unsafe { alloc.dealloc(ptr, layout) };
+            } else {
+                let layout = Layout::from_size_align(rng.next(300), 1 << rng.next(7)).unwrap();
+                if let Ok(ptr) = alloc.alloc_layout(layout) {
+                    let ptr = ptr.cast::<u8>();
+                    assert!((ptr.as_ptr() as usize).is_multiple_of(layout.align()));
+                    unsafe { ptr.write_bytes(step as u8, layout.size()) };
+                    live.push((ptr, layout, step as u8));
+                }
+            }
+        }
+
+        for (ptr, layout, _) in live {
+            unsafe { alloc.dealloc(ptr, layout) };
+        }
+        assert_eq!(alloc.free_count(alloc.orders() - 1), 1);
+        assert_eq!(alloc.free_bytes(), 4096);
+    }
+}
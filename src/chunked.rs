@@ -0,0 +1,203 @@
+use alloc::alloc::{alloc, dealloc};
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::ptr::NonNull;
+
+use crate::{Alloc, AllocResult, OutOfMemory};
+
+const DEFAULT_CHUNK_SIZE: usize = 4096;
+
+/// Bump allocator over a chain of chunks taken from the global allocator.
+/// Regular chunks grow geometrically; objects larger than half the next chunk
+/// get a dedicated chunk. All chunks are freed on drop, destructors of the
+/// allocated values are not run.
+pub struct ChunkedAlloc {
+    current: Cell<Option<NonNull<Chunk>>>,
+    next_chunk_size: Cell<usize>,
+    allocated: Cell<usize>,
+    max_size: usize,
+}
+
+// Chunk header, followed by the memory its `alloc` bumps through
+struct Chunk {
+    prev: Option<NonNull<Chunk>>,
+    layout: Layout,
+    alloc: Alloc<'static>,
+}
+
+// The chunks are owned, and values in them are never dropped by the allocator
+unsafe impl Send for ChunkedAlloc {}
+
+impl ChunkedAlloc {
+    pub fn new() -> Self {
+        Self::with_limits(DEFAULT_CHUNK_SIZE, usize::MAX)
+    }
+
+    // `max_size` bounds the total bytes requested from the global allocator, headers included
+    pub fn with_limits(first_chunk_size: usize, max_size: usize) -> Self {
+        ChunkedAlloc {
+            current: Cell::new(None),
+            next_chunk_size: Cell::new(first_chunk_size.max(1)),
+            allocated: Cell::new(0),
+            max_size,
+        }
+    }
+
+    /// Total bytes requested from the global allocator.
+    pub fn allocated_bytes(&self) -> usize {
+        self.allocated.get()
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        let mut count = 0;
+        let mut chunk = self.current.get();
+        while let Some(ptr) = chunk {
+            count += 1;
+            chunk = unsafe { ptr.as_ref().prev };
+        }
+        count
+    }
+
+    pub fn alloc_layout(&self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+        self.chunk_for(layout)?.alloc_layout(layout)
+    }
+
+    // Returns a chunk with room for `layout`, adding one if needed
+    fn chunk_for(&self, layout: Layout) -> AllocResult<&Alloc<'static>> {
+        if let Some(chunk) = self.current.get() {
+            let alloc = unsafe { &(*chunk.as_ptr()).alloc };
+            if alloc.fits(layout) { return Ok(alloc); }
+        }
+
+        // Worst case alignment padding inside the new chunk
+        let required = layout.size().checked_add(layout.align() - 1).ok_or(OutOfMemory)?;
+        let next_chunk_size = self.next_chunk_size.get();
+        let chunk = if required > next_chunk_size / 2 {
+            let chunk = self.new_chunk(required)?;
+            self.link_dedicated(chunk);
+            chunk
+        } else {
+            let chunk = self.new_chunk(next_chunk_size)?;
+            unsafe { (*chunk.as_ptr()).prev = self.current.get() };
+            self.current.set(Some(chunk));
+            self.next_chunk_size.set(next_chunk_size.saturating_mul(2));
+            chunk
+        };
+        Ok(unsafe { &(*chunk.as_ptr()).alloc })
+    }
+
+    // Dedicated chunks go below the current one so it keeps serving small allocations
+    fn link_dedicated(&self, chunk: NonNull<Chunk>) {
+        match self.current.get() {
+            Some(current) => unsafe {
+                (*chunk.as_ptr()).prev = (*current.as_ptr()).prev;
+                (*current.as_ptr()).prev = Some(chunk);
+            },
+            None => self.current.set(Some(chunk)),
+        }
+    }
+
+    fn new_chunk(&self, capacity: usize) -> AllocResult<NonNull<Chunk>> {
+        let (layout, offset) = Layout::new::<Chunk>()
+            .extend(Layout::array::<u8>(capacity).map_err(|_| OutOfMemory)?)
+            .map_err(|_| OutOfMemory)?;
+        let allocated = self.allocated.get().checked_add(layout.size()).ok_or(OutOfMemory)?;
+        if allocated > self.max_size { return Err(OutOfMemory); }
+
+        let base = NonNull::new(unsafe { alloc(layout) }).ok_or(OutOfMemory)?;
+        self.allocated.set(allocated);
+        unsafe {
+            let heap = core::slice::from_raw_parts_mut(base.as_ptr().add(offset), capacity);
+            let chunk = base.cast::<Chunk>();
+            chunk.write(Chunk { prev: None, layout, alloc: Alloc::new(heap) });
+            Ok(chunk)
+        }
+    }
+}
+
+impl Default for ChunkedAlloc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for ChunkedAlloc {
+    fn drop(&mut self) {
+        let mut chunk = self.current.get();
+        while let Some(ptr) = chunk {
+            unsafe {
+                let Chunk { prev, layout, .. } = ptr.read();
+                dealloc(ptr.as_ptr().cast(), layout);
+                chunk = prev;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ArenaAllocator;
+
+    #[test]
+    fn alloc_across_chunks() {
+        let alloc = ChunkedAlloc::with_limits(64, usize::MAX);
+        let values: std::vec::Vec<&mut u64> = (0..64).map(|i| alloc.alloc(i).unwrap()).collect();
+        assert!(alloc.chunk_count() > 1);
+        assert!(values.iter().enumerate().all(|(i, v)| **v == i as u64));
+        assert!(values.iter().all(|v| (*v as *const u64 as usize).is_multiple_of(8)));
+    }
+
+    #[test]
+    fn chunks_grow_geometrically() {
+        let alloc = ChunkedAlloc::with_limits(64, usize::MAX);
+        alloc.alloc_slice_copy(&[0u8; 30]).unwrap();
+        let first = alloc.allocated_bytes();
+        alloc.alloc_slice_copy(&[0u8; 30]).unwrap();
+        alloc.alloc_slice_copy(&[0u8; 30]).unwrap();
+        let second = alloc.allocated_bytes() - first;
+        assert_eq!(alloc.chunk_count(), 2);
+        assert_eq!(second - first, 64);
+    }
+
+    #[test]
+    fn huge_object_gets_dedicated_chunk() {
+        let alloc = ChunkedAlloc::with_limits(64, usize::MAX);
+        let small = alloc.alloc(1u8).unwrap() as *mut u8;
+        let huge = alloc.alloc_from_fn(1000, |i| i as u16).unwrap();
+        assert_eq!(huge[999], 999);
+        assert_eq!(alloc.chunk_count(), 2);
+        let next_small = alloc.alloc(2u8).unwrap() as *mut u8;
+        assert_eq!(next_small, small.wrapping_add(1));
+    }
+
+    #[test]
+    fn max_size_limits_chunks() {
+        let alloc = ChunkedAlloc::with_limits(64, 256);
+        assert_eq!(alloc.alloc_slice_copy(&[0u8; 512]), Err(OutOfMemory));
+        let mut count = 0;
+        while alloc.alloc([0u8; 16]).is_ok() {
+            count += 1;
+        }
+        assert!(count >= 4);
+        assert!(alloc.allocated_bytes() <= 256);
+    }
+
+    #[test]
+    fn strings_and_fmt() {
+        let alloc = ChunkedAlloc::with_limits(16, usize::MAX);
+        let a = alloc.alloc_str("chunked").unwrap();
+        let b = alloc.alloc_fmt(format_args!("{}-{}", a, 42)).unwrap();
+        let c = alloc.alloc_fmt(format_args!("a much longer string than the first chunk")).unwrap();
+        assert_eq!((&*a, &*b), ("chunked", "chunked-42"));
+        assert_eq!(c.len(), 41);
+    }
+
+    #[test]
+    fn nested_alloc_from_fn() {
+        let alloc = ChunkedAlloc::with_limits(32, usize::MAX);
+        let rows = alloc.alloc_from_fn(8, |i| &*alloc.alloc_from_fn(8, |j| i * j).unwrap()).unwrap();
+        assert_eq!(rows[7][7], 49);
+        assert_eq!(rows[3], [0, 3, 6, 9, 12, 15, 18, 21]);
+    }
+}
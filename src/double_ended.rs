@@ -0,0 +1,225 @@
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+use crate::{align_padding, fill_slice, AllocResult, OutOfMemory};
+
+/// Front cursor position saved by `DoubleEndedAlloc::checkpoint_front`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrontCheckpoint {
+    offset: usize,
+}
+
+/// Back cursor position saved by `DoubleEndedAlloc::checkpoint_back`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackCheckpoint {
+    offset: usize,
+}
+
+/// Bump allocator working from both ends of one heap: the front grows up,
+/// for short-lived data, and the back grows down, for long-lived data.
+/// It is out of memory when the two cursors would cross.
+pub struct DoubleEndedAlloc<'mem> {
+    heap: *mut u8,
+    size: usize,
+    front: Cell<usize>,
+    back: Cell<usize>,
+    _mem: PhantomData<&'mem mut [u8]>,
+}
+
+unsafe impl Send for DoubleEndedAlloc<'_> {}
+
+impl<'mem> DoubleEndedAlloc<'mem> {
+    pub fn new(heap: &'mem mut [u8]) -> Self {
+        DoubleEndedAlloc {
+            heap: heap.as_mut_ptr(),
+            size: heap.len(),
+            front: Cell::new(0),
+            back: Cell::new(heap.len()),
+            _mem: PhantomData,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.size
+    }
+
+    pub fn used_front(&self) -> usize {
+        self.front.get()
+    }
+
+    pub fn used_back(&self) -> usize {
+        self.size - self.back.get()
+    }
+
+    /// Free space between the two cursors.
+    pub fn remaining(&self) -> usize {
+        self.back.get() - self.front.get()
+    }
+
+    pub fn alloc_front<'item, T>(&self, item: T) -> AllocResult<&'item mut T>
+        where 'mem: 'item
+    {
+        let item_ptr = self.reserve_front(Layout::new::<T>())?.cast::<T>().as_ptr();
+        unsafe {
+            item_ptr.write(item);
+            Ok(&mut *item_ptr)
+        }
+    }
+
+    pub fn alloc_back<'item, T>(&self, item: T) -> AllocResult<&'item mut T>
+        where 'mem: 'item
+    {
+        let item_ptr = self.reserve_back(Layout::new::<T>())?.cast::<T>().as_ptr();
+        unsafe {
+            item_ptr.write(item);
+            Ok(&mut *item_ptr)
+        }
+    }
+
+    pub fn alloc_from_fn_front<'item, T>(&self, size: usize, mut f: impl FnMut(usize) -> T) -> AllocResult<&'item mut [T]>
+        where 'mem: 'item
+    {
+        let layout = Layout::array::<T>(size).map_err(|_| OutOfMemory)?;
+        let arr_ptr = self.reserve_front(layout)?.cast::<T>().as_ptr();
+        unsafe { fill_slice(arr_ptr, size, |i| Ok(f(i))) }
+    }
+
+    pub fn alloc_from_fn_back<'item, T>(&self, size: usize, mut f: impl FnMut(usize) -> T) -> AllocResult<&'item mut [T]>
+        where 'mem: 'item
+    {
+        let layout = Layout::array::<T>(size).map_err(|_| OutOfMemory)?;
+        let arr_ptr = self.reserve_back(layout)?.cast::<T>().as_ptr();
+        unsafe { fill_slice(arr_ptr, size, |i| Ok(f(i))) }
+    }
+
+    pub fn alloc_layout_front(&self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+        let ptr = self.reserve_front(layout)?;
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    pub fn alloc_layout_back(&self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+        let ptr = self.reserve_back(layout)?;
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    pub fn checkpoint_front(&self) -> FrontCheckpoint {
+        FrontCheckpoint { offset: self.front.get() }
+    }
+
+    pub fn checkpoint_back(&self) -> BackCheckpoint {
+        BackCheckpoint { offset: self.back.get() }
+    }
+
+    /// Releases everything allocated from the front since `checkpoint`;
+    /// the back is left untouched.
+    ///
+    /// # Safety
+    ///
+    /// Nothing allocated from the front after `checkpoint` may be used after the rewind.
+    pub unsafe fn rewind_front(&mut self, checkpoint: FrontCheckpoint) {
+        debug_assert!(checkpoint.offset <= self.front.get(), "rewind past the front cursor");
+        self.front.set(checkpoint.offset);
+    }
+
+    /// Releases everything allocated from the back since `checkpoint`;
+    /// the front is left untouched.
+    ///
+    /// # Safety
+    ///
+    /// Nothing allocated from the back after `checkpoint` may be used after the rewind.
+    pub unsafe fn rewind_back(&mut self, checkpoint: BackCheckpoint) {
+        debug_assert!(checkpoint.offset >= self.back.get(), "rewind past the back cursor");
+        self.back.set(checkpoint.offset);
+    }
+
+    // Consumes alignment padding plus `layout.size()` bytes above the front cursor, or nothing on error
+    fn reserve_front(&self, layout: Layout) -> AllocResult<NonNull<u8>> {
+        let front = self.front.get();
+        let start = front + align_padding(self.heap as usize + front, layout.align());
+        let end = start.checked_add(layout.size()).ok_or(OutOfMemory)?;
+        if end > self.back.get() { return Err(OutOfMemory); }
+
+        self.front.set(end);
+        Ok(unsafe { NonNull::new_unchecked(self.heap.add(start)) })
+    }
+
+    // Consumes `layout.size()` bytes plus alignment padding below the back cursor, or nothing on error
+    fn reserve_back(&self, layout: Layout) -> AllocResult<NonNull<u8>> {
+        let end = self.back.get().checked_sub(layout.size()).ok_or(OutOfMemory)?;
+        let start = end.checked_sub((self.heap as usize + end) % layout.align()).ok_or(OutOfMemory)?;
+        if start < self.front.get() { return Err(OutOfMemory); }
+
+        self.back.set(start);
+        Ok(unsafe { NonNull::new_unchecked(self.heap.add(start)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Heap;
+
+    #[test]
+    fn alloc_from_both_ends() {
+        let mut heap = Heap([0u8; 64]);
+        let range = heap.0.as_ptr_range();
+        let alloc = DoubleEndedAlloc::new(&mut heap.0);
+        let temp = alloc.alloc_front(1u32).unwrap() as *mut u32 as *const u8;
+        let kept = alloc.alloc_back(2u32).unwrap() as *mut u32 as *const u8;
+        let older = alloc.alloc_back(3u32).unwrap() as *mut u32 as *const u8;
+        assert_eq!(temp, range.start);
+        assert_eq!(kept, range.end.wrapping_sub(4));
+        assert_eq!(older, range.end.wrapping_sub(8));
+        assert_eq!((alloc.used_front(), alloc.used_back(), alloc.remaining()), (4, 8, 52));
+    }
+
+    #[test]
+    fn both_ends_aligned() {
+        let mut heap = Heap([0u8; 64]);
+        let alloc = DoubleEndedAlloc::new(&mut heap.0);
+        alloc.alloc_front(1u8).unwrap();
+        alloc.alloc_back(1u8).unwrap();
+        let front = alloc.alloc_front(2u64).unwrap();
+        let back = alloc.alloc_back(3u64).unwrap();
+        assert!((front as *mut u64 as usize).is_multiple_of(8));
+        assert!((back as *mut u64 as usize).is_multiple_of(8));
+        assert_eq!((alloc.used_front(), alloc.used_back()), (16, 16));
+        let slice = alloc.alloc_from_fn_back(3, |i| i as u16).unwrap();
+        assert_eq!(slice, &[0, 1, 2]);
+    }
+
+    #[test]
+    fn out_of_memory_when_cursors_meet() {
+        let mut heap = Heap([0u8; 16]);
+        let alloc = DoubleEndedAlloc::new(&mut heap.0);
+        alloc.alloc_front([0u8; 6]).unwrap();
+        alloc.alloc_back([0u8; 6]).unwrap();
+        assert_eq!(alloc.alloc_back(0u32), Err(OutOfMemory));
+        assert_eq!(alloc.alloc_front(0u32), Err(OutOfMemory));
+        assert_eq!(alloc.remaining(), 4);
+        alloc.alloc_back([0u8; 4]).unwrap();
+        assert_eq!(alloc.alloc_front(0u8), Err(OutOfMemory));
+        assert_eq!(alloc.alloc_layout_back(Layout::new::<[u8; 32]>()), Err(OutOfMemory));
+    }
+
+    #[test]
+    fn ends_rewind_independently() {
+        let mut heap = Heap([0u8; 32]);
+        let mut alloc = DoubleEndedAlloc::new(&mut heap.0);
+        let front = alloc.checkpoint_front();
+        alloc.alloc_front([0u8; 12]).unwrap();
+        let back = alloc.checkpoint_back();
+        alloc.alloc_back([0u8; 12]).unwrap();
+        let kept = alloc.checkpoint_back();
+        alloc.alloc_back([0u8; 8]).unwrap();
+
+        unsafe { alloc.rewind_front(front) };
+        assert_eq!((alloc.used_front(), alloc.used_back()), (0, 20));
+        unsafe { alloc.rewind_back(kept) };
+        assert_eq!(alloc.used_back(), 12);
+        unsafe { alloc.rewind_back(back) };
+        assert_eq!(alloc.remaining(), 32);
+    }
+}
@@ -0,0 +1,96 @@
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::ptr::{self, NonNull};
+
+// Intrusive list of values whose destructors must run, stored in the heap
+// next to the values themselves. The head is the most recent allocation.
+pub(crate) struct DropList {
+    head: Cell<Option<NonNull<DropEntry>>>,
+}
+
+struct DropEntry {
+    next: Option<NonNull<DropEntry>>,
+    drop_fn: unsafe fn(NonNull<DropEntry>),
+}
+
+#[repr(C)]
+pub(crate) struct Tracked<T> {
+    entry: DropEntry,
+    pub(crate) item: T,
+}
+
+impl<T> Tracked<T> {
+    pub(crate) fn new(item: T) -> Self {
+        Tracked { entry: DropEntry { next: None, drop_fn: drop_tracked::<T> }, item }
+    }
+}
+
+unsafe fn drop_tracked<T>(entry: NonNull<DropEntry>) {
+    let tracked = entry.cast::<Tracked<T>>().as_ptr();
+    unsafe { ptr::drop_in_place(ptr::addr_of_mut!((*tracked).item)) }
+}
+
+// Header of a slice whose `len` elements follow it in the heap
+#[repr(C)]
+pub(crate) struct TrackedSlice {
+    entry: DropEntry,
+    pub(crate) len: usize,
+}
+
+impl TrackedSlice {
+    pub(crate) fn new<T>() -> Self {
+        TrackedSlice { entry: DropEntry { next: None, drop_fn: drop_tracked_slice::<T> }, len: 0 }
+    }
+
+    // Layout of the header followed by `len` elements
+    pub(crate) fn layout<T>(len: usize) -> Option<Layout> {
+        let (layout, _) = Layout::new::<TrackedSlice>().extend(Layout::array::<T>(len).ok()?).ok()?;
+        Some(layout)
+    }
+
+    // First element, placed right after the header at the alignment of `T`
+    pub(crate) fn items<T>(header: NonNull<TrackedSlice>) -> *mut T {
+        let offset = Layout::new::<TrackedSlice>().size().next_multiple_of(core::mem::align_of::<T>());
+        unsafe { header.cast::<u8>().as_ptr().add(offset).cast() }
+    }
+}
+
+unsafe fn drop_tracked_slice<T>(entry: NonNull<DropEntry>) {
+    let header = entry.cast::<TrackedSlice>();
+    let len = unsafe { (*header.as_ptr()).len };
+    unsafe { ptr::drop_in_place(ptr::slice_from_raw_parts_mut(TrackedSlice::items::<T>(header), len)) }
+}
+
+impl DropList {
+    pub(crate) const fn new() -> Self {
+        DropList { head: Cell::new(None) }
+    }
+
+    // Safety: `tracked` must stay valid until its destructor is run by `run_from`
+    pub(crate) unsafe fn push<T>(&self, tracked: NonNull<Tracked<T>>) {
+        unsafe { self.push_entry(tracked.cast()) }
+    }
+
+    // Safety: as for `push`, and the header's `len` elements must be initialized
+    pub(crate) unsafe fn push_slice(&self, header: NonNull<TrackedSlice>) {
+        unsafe { self.push_entry(header.cast()) }
+    }
+
+    unsafe fn push_entry(&self, entry: NonNull<DropEntry>) {
+        unsafe { (*entry.as_ptr()).next = self.head.get() };
+        self.head.set(Some(entry));
+    }
+
+    // Runs destructors of the entries placed at or above `start`, newest first.
+    // Each entry is unlinked before its destructor runs, so a panic cannot drop twice.
+    // Safety: those values must not be used afterwards
+    pub(crate) unsafe fn run_from(&self, start: *const u8) {
+        while let Some(entry) = self.head.get() {
+            if (entry.as_ptr() as *const u8) < start { break; }
+            unsafe {
+                self.head.set((*entry.as_ptr()).next);
+                ((*entry.as_ptr()).drop_fn)(entry);
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::ptr::NonNull;
+
+use crate::{AllocResult, ArenaAllocator, OutOfMemory};
+
+/// Allocation counters of one `FallbackAlloc` tier.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TierStats {
+    pub allocations: usize,
+    pub bytes: usize,
+    pub failures: usize,
+}
+
+/// Tries `primary` first and allocates from `fallback` when it is out of memory.
+pub struct FallbackAlloc<A, B> {
+    primary: A,
+    fallback: B,
+    primary_stats: Cell<TierStats>,
+    fallback_stats: Cell<TierStats>,
+}
+
+impl<A: ArenaAllocator, B: ArenaAllocator> FallbackAlloc<A, B> {
+    pub fn new(primary: A, fallback: B) -> Self {
+        FallbackAlloc {
+            primary,
+            fallback,
+            primary_stats: Cell::new(TierStats::default()),
+            fallback_stats: Cell::new(TierStats::default()),
+        }
+    }
+
+    pub fn primary(&self) -> &A {
+        &self.primary
+    }
+
+    pub fn fallback(&self) -> &B {
+        &self.fallback
+    }
+
+    pub fn primary_stats(&self) -> TierStats {
+        self.primary_stats.get()
+    }
+
+    pub fn fallback_stats(&self) -> TierStats {
+        self.fallback_stats.get()
+    }
+
+    pub fn into_inner(self) -> (A, B) {
+        (self.primary, self.fallback)
+    }
+}
+
+fn record(stats: &Cell<TierStats>, layout: Layout, result: &AllocResult<NonNull<[u8]>>) {
+    let mut tier = stats.get();
+    match result {
+        Ok(_) => {
+            tier.allocations += 1;
+            tier.bytes += layout.size();
+        }
+        Err(OutOfMemory) => tier.failures += 1,
+    }
+    stats.set(tier);
+}
+
+unsafe impl<A: ArenaAllocator, B: ArenaAllocator> ArenaAllocator for FallbackAlloc<A, B> {
+    fn alloc_layout(&self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+        let result = self.primary.alloc_layout(layout);
+        record(&self.primary_stats, layout, &result);
+        if result.is_ok() { return result; }
+
+        let result = self.fallback.alloc_layout(layout);
+        record(&self.fallback_stats, layout, &result);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Alloc;
+
+    #[test]
+    fn falls_back_when_primary_is_full() {
+        let mut sram: [u8; 8] = core::array::from_fn(|_| 0);
+        let mut psram: [u8; 64] = core::array::from_fn(|_| 0);
+        let sram_range = sram.as_ptr_range();
+        let psram_range = psram.as_ptr_range();
+        let alloc = FallbackAlloc::new(Alloc::new(&mut sram), Alloc::new(&mut psram));
+
+        let fast = alloc.alloc_from_fn(8, |i| i as u8).unwrap();
+        let slow = alloc.alloc(7u32).unwrap();
+        assert!(sram_range.contains(&fast.as_ptr()));
+        assert!(psram_range.contains(&(slow as *mut u32 as *const u8)));
+        assert_eq!((fast[7], *slow), (7, 7));
+
+        assert_eq!(alloc.primary_stats(), TierStats { allocations: 1, bytes: 8, failures: 1 });
+        assert_eq!(alloc.fallback_stats(), TierStats { allocations: 1, bytes: 4, failures: 0 });
+    }
+
+    #[test]
+    fn out_of_memory_when_both_are_full() {
+        let mut sram: [u8; 4] = core::array::from_fn(|_| 0);
+        let mut psram: [u8; 4] = core::array::from_fn(|_| 0);
+        let alloc = FallbackAlloc::new(Alloc::new(&mut sram), Alloc::new(&mut psram));
+        assert!(alloc.alloc([0u8; 4]).is_ok());
+        assert!(alloc.alloc([0u8; 4]).is_ok());
+        assert_eq!(alloc.alloc(0u8), Err(OutOfMemory));
+        assert_eq!(alloc.primary_stats().failures, 2);
+        assert_eq!(alloc.fallback_stats().failures, 1);
+    }
+
+    #[test]
+    fn borrowed_tiers_and_nesting() {
+        let mut a: [u8; 2] = core::array::from_fn(|_| 0);
+        let mut b: [u8; 2] = core::array::from_fn(|_| 0);
+        let mut c: [u8; 2] = core::array::from_fn(|_| 0);
+        let (a, b, c) = (Alloc::new(&mut a), Alloc::new(&mut b), Alloc::new(&mut c));
+        let alloc = FallbackAlloc::new(FallbackAlloc::new(&a, &b), &c);
+        for i in 0..3u8 {
+            assert_eq!(*alloc.alloc([i; 2]).unwrap(), [i; 2]);
+        }
+        assert_eq!(alloc.alloc(0u8), Err(OutOfMemory));
+        assert_eq!(alloc.primary().primary_stats().allocations, 1);
+        assert_eq!(alloc.primary().fallback_stats().allocations, 1);
+        assert_eq!(alloc.fallback_stats().allocations, 1);
+    }
+}
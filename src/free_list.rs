@@ -0,0 +1,485 @@
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+use crate::{align_padding, AllocResult, ArenaAllocator, OutOfMemory};
+
+// Every block starts with a header word holding its total size; the low bit
+// marks free blocks. Free blocks keep the offset of the next free block in
+// their second word, and the free list is sorted by address
+const WORD: usize = core::mem::size_of::<usize>();
+const HEADER: usize = WORD;
+const MIN_BLOCK: usize = 2 * WORD;
+const FREE: usize = 1;
+const NIL: usize = usize::MAX;
+
+/// How `FreeListAlloc` picks a free block for an allocation.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FitPolicy {
+    /// The lowest-addressed block that fits.
+    #[default]
+    FirstFit,
+    /// The smallest block that fits.
+    BestFit,
+}
+
+/// General-purpose allocator over a borrowed heap: allocations can be freed
+/// and resized, and freed blocks are coalesced with free neighbours.
+pub struct FreeListAlloc<'mem> {
+    heap: *mut u8,
+    size: usize,
+    head: Cell<usize>,
+    policy: FitPolicy,
+    _mem: PhantomData<&'mem mut [u8]>,
+}
+
+unsafe impl Send for FreeListAlloc<'_> {}
+
+impl<'mem> FreeListAlloc<'mem> {
+    pub fn new(heap: &'mem mut [u8]) -> Self {
+        Self::with_policy(heap, FitPolicy::default())
+    }
+
+    pub fn with_policy(heap: &'mem mut [u8], policy: FitPolicy) -> Self {
+        let padding = align_padding(heap.as_ptr() as usize, WORD).min(heap.len());
+        let size = (heap.len() - padding) / WORD * WORD;
+        let alloc = FreeListAlloc {
+            heap: unsafe { heap.as_mut_ptr().add(padding) },
+            size: if size < MIN_BLOCK { 0 } else { size },
+            head: Cell::new(NIL),
+            policy,
+            _mem: PhantomData,
+        };
+        if alloc.size > 0 {
+            alloc.set_word(0, alloc.size | FREE);
+            alloc.set_word(WORD, NIL);
+            alloc.head.set(0);
+        }
+        alloc
+    }
+
+    pub fn policy(&self) -> FitPolicy {
+        self.policy
+    }
+
+    /// Usable heap size, after aligning the borrowed slice to a word;
+    /// zero when it cannot hold a single block.
+    pub fn capacity(&self) -> usize {
+        self.size
+    }
+
+    /// Total size of the free blocks, headers included.
+    pub fn free_bytes(&self) -> usize {
+        self.free_blocks().map(|(_, size)| size).sum()
+    }
+
+    pub fn alloc_layout(&self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+        let mut found = None;
+        for (offset, size) in self.free_blocks() {
+            let Some(range) = self.place(offset, size, layout) else { continue };
+            match (self.policy, found) {
+                (FitPolicy::BestFit, Some((_, best_size, _))) if best_size <= size => {}
+                _ => found = Some((offset, size, range)),
+            }
+            if self.policy == FitPolicy::FirstFit { break; }
+        }
+        let (offset, size, (start, end)) = found.ok_or(OutOfMemory)?;
+
+        self.unlink(offset);
+        if start > offset {
+            self.insert_free(offset, start - offset);
+        }
+        let end = if offset + size - end >= MIN_BLOCK {
+            self.insert_free(end, offset + size - end);
+            end
+        } else {
+            offset + size
+        };
+        self.set_word(start, end - start);
+        Ok(NonNull::slice_from_raw_parts(self.payload(start), layout.size()))
+    }
+
+    /// Returns the block at `ptr` to the free list, merging it with free neighbours.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must come from this allocator, not be freed yet, and nothing
+    /// may use the allocation afterwards.
+    pub unsafe fn dealloc(&self, ptr: NonNull<u8>, layout: Layout) {
+        let start = self.block_of(ptr);
+        let size = self.word(start);
+        debug_assert!(size & FREE == 0, "double free");
+        debug_assert!(size - HEADER >= layout.size(), "layout does not match the allocation");
+        self.insert_free(start, size);
+    }
+
+    /// Resizes the allocation at `ptr`, in place when the block or its free
+    /// right neighbour has room, otherwise by moving it. On error the old
+    /// allocation is left untouched.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a live allocation of this allocator made with `old_layout`.
+    /// If the allocation moves, the old pointer is freed.
+    pub unsafe fn realloc(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> AllocResult<NonNull<u8>> {
+        let start = self.block_of(ptr);
+        let size = self.word(start);
+        let aligned = (ptr.as_ptr() as usize).is_multiple_of(new_layout.align());
+        let new_end = payload_size(new_layout.size()).and_then(|payload| (start + HEADER).checked_add(payload));
+
+        if let (true, Some(new_end)) = (aligned, new_end) {
+            let mut end = start + size;
+            if new_end > end && end < self.size && self.word(end) & FREE != 0 && end + self.size_of(end) >= new_end {
+                let next_size = self.size_of(end);
+                self.unlink(end);
+                end += next_size;
+            }
+            if new_end <= end {
+                if end - new_end >= MIN_BLOCK {
+                    self.insert_free(new_end, end - new_end);
+                    end = new_end;
+                }
+                self.set_word(start, end - start);
+                return Ok(ptr);
+            }
+        }
+
+        let new_ptr = self.alloc_layout(new_layout)?.cast::<u8>();
+        let copy_size = old_layout.size().min(new_layout.size());
+        unsafe {
+            core::ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_ptr(), copy_size);
+            self.dealloc(ptr, old_layout);
+        }
+        Ok(new_ptr)
+    }
+
+    /// Checks the heap structure: blocks tile the heap, free neighbours are
+    /// merged, and the free list is sorted and holds exactly the free blocks.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        let mut offset = 0;
+        let mut free_count = 0;
+        let mut prev_free = false;
+        while offset < self.size {
+            let size = self.size_of(offset);
+            if size < MIN_BLOCK || !size.is_multiple_of(WORD) || size > self.size - offset {
+                return Err("corrupted block size");
+            }
+            let free = self.word(offset) & FREE != 0;
+            if free && prev_free { return Err("adjacent free blocks were not merged"); }
+            free_count += free as usize;
+            prev_free = free;
+            offset += size;
+        }
+
+        let mut listed = 0;
+        let mut prev = None;
+        let mut offset = self.head.get();
+        while offset != NIL {
+            if offset >= self.size || self.word(offset) & FREE == 0 {
+                return Err("free list entry is not a free block");
+            }
+            if prev.is_some_and(|prev| prev >= offset) { return Err("free list is not sorted by address"); }
+            listed += 1;
+            if listed > free_count { return Err("free list has more entries than free blocks"); }
+            prev = Some(offset);
+            offset = self.word(offset + WORD);
+        }
+        if listed != free_count { return Err("free block missing from the free list"); }
+        Ok(())
+    }
+
+    // Block start and end for `layout` inside the free block at `offset`. A gap
+    // in front of the allocation must be large enough to remain a free block
+    fn place(&self, offset: usize, size: usize, layout: Layout) -> Option<(usize, usize)> {
+        let align = layout.align().max(WORD);
+        let mut payload = offset + HEADER;
+        payload += align_padding(self.heap as usize + payload, align);
+        if payload - HEADER != offset && payload - HEADER - offset < MIN_BLOCK {
+            payload = offset + HEADER + MIN_BLOCK;
+            payload += align_padding(self.heap as usize + payload, align);
+        }
+        let end = payload.checked_add(payload_size(layout.size())?)?;
+        (end <= offset + size).then_some((payload - HEADER, end))
+    }
+
+    // Adds a free block, merging it with the free blocks right before and after it
+    fn insert_free(&self, mut offset: usize, mut size: usize) {
+        let mut prev = NIL;
+        let mut next = self.head.get();
+        while next != NIL && next < offset {
+            prev = next;
+            next = self.word(next + WORD);
+        }
+
+        if next != NIL && offset + size == next {
+            size += self.size_of(next);
+            next = self.word(next + WORD);
+        }
+        if prev != NIL && prev + self.size_of(prev) == offset {
+            size += self.size_of(prev);
+            offset = prev;
+        } else if prev != NIL {
+            self.set_word(prev + WORD, offset);
+        } else {
+            self.head.set(offset);
+        }
+        self.set_word(offset, size | FREE);
+        self.set_word(offset + WORD, next);
+    }
+
+    fn unlink(&self, offset: usize) {
+        let next = self.word(offset + WORD);
+        if self.head.get() == offset {
+            self.head.set(next);
+            return;
+        }
+        let mut prev = self.head.get();
+        while self.word(prev + WORD) != offset {
+            prev = self.word(prev + WORD);
+        }
+        self.set_word(prev + WORD, next);
+    }
+
+    fn free_blocks(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let mut offset = self.head.get();
+        core::iter::from_fn(move || {
+            if offset == NIL { return None; }
+            let block = (offset, self.size_of(offset));
+            offset = self.word(offset + WORD);
+            Some(block)
+        })
+    }
+
+    fn block_of(&self, ptr: NonNull<u8>) -> usize {
+        ptr.as_ptr() as usize - self.heap as usize - HEADER
+    }
+
+    fn payload(&self, start: usize) -> NonNull<u8> {
+        unsafe { NonNull::new_unchecked(self.heap.add(start + HEADER)) }
+    }
+
+    fn size_of(&self, offset: usize) -> usize {
+        self.word(offset) & !FREE
+    }
+
+    fn word(&self, offset: usize) -> usize {
+        unsafe { self.heap.add(offset).cast::<usize>().read() }
+    }
+
+    fn set_word(&self, offset: usize, value: usize) {
+        unsafe { self.heap.add(offset).cast::<usize>().write(value) }
+    }
+}
+
+// Payload rounded up to whole words, at least one so the block can hold a free list link
+fn payload_size(size: usize) -> Option<usize> {
+    Some(size.max(WORD).checked_add(WORD - 1)? / WORD * WORD)
+}
+
+unsafe impl ArenaAllocator for FreeListAlloc<'_> {
+    fn alloc_layout(&self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+        FreeListAlloc::alloc_layout(self, layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{Heap, Rng};
+    use std::vec::Vec;
+
+    fn addr(ptr: NonNull<[u8]>) -> usize {
+        ptr.cast::<u8>().as_ptr() as usize
+    }
+
+    #[test]
+    fn alloc_and_free_reuses_memory() {
+        let mut heap = Heap([0u8; 256]);
+        let alloc = FreeListAlloc::new(&mut heap.0);
+        let layout = Layout::new::<[u64; 4]>();
+        let first = alloc.alloc_layout(layout).unwrap();
+        unsafe { alloc.dealloc(first.cast(), layout) };
+        let second = alloc.alloc_layout(layout).unwrap();
+        assert_eq!(addr(first), addr(second));
+        alloc.validate().unwrap();
+    }
+
+    #[test]
+    fn free_coalesces_neighbours() {
+        let mut heap = Heap([0u8; 256]);
+        let alloc = FreeListAlloc::new(&mut heap.0);
+        let layout = Layout::new::<[u8; 32]>();
+        let blocks: Vec<_> = (0..3).map(|_| alloc.alloc_layout(layout).unwrap()).collect();
+        unsafe {
+            alloc.dealloc(blocks[0].cast(), layout);
+            alloc.dealloc(blocks[2].cast(), layout);
+            alloc.validate().unwrap();
+            alloc.dealloc(blocks[1].cast(), layout);
+        }
+        alloc.validate().unwrap();
+        assert_eq!(alloc.free_blocks().count(), 1);
+        assert_eq!(alloc.free_bytes(), alloc.capacity());
+    }
+
+    #[test]
+    fn first_fit_and_best_fit() {
+        let big = Layout::new::<[u8; 64]>();
+        let small = Layout::new::<[u8; 16]>();
+        for (policy, expected) in [(FitPolicy::FirstFit, 0), (FitPolicy::BestFit, 2)] {
+            let mut heap = Heap([0u8; 512]);
+            let alloc = FreeListAlloc::with_policy(&mut heap.0, policy);
+            let blocks: Vec<_> = [big, small, small, small].iter().map(|&l| alloc.alloc_layout(l).unwrap()).collect();
+            unsafe {
+                alloc.dealloc(blocks[0].cast(), big);
+                alloc.dealloc(blocks[2].cast(), small);
+            }
+            assert_eq!(addr(alloc.alloc_layout(small).unwrap()), addr(blocks[expected]));
+            alloc.validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn alloc_over_aligned() {
+        let mut heap = Heap([0u8; 1024]);
+        let alloc = FreeListAlloc::new(&mut heap.0);
+        alloc.alloc_layout(Layout::new::<u8>()).unwrap();
+        for align in [16, 32, 64, 128] {
+            let ptr = alloc.alloc_layout(Layout::from_size_align(24, align).unwrap()).unwrap();
+            assert!(addr(ptr).is_multiple_of(align));
+            alloc.validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn out_of_memory() {
+        let mut heap = Heap([0u8; 64]);
+        let alloc = FreeListAlloc::new(&mut heap.0);
+        assert_eq!(alloc.alloc_layout(Layout::new::<[u8; 64]>()), Err(OutOfMemory));
+        assert_eq!(alloc.alloc_layout(Layout::from_size_align(isize::MAX as usize - 7, 8).unwrap()), Err(OutOfMemory));
+        assert!(alloc.alloc_layout(Layout::new::<[u8; 56]>()).is_ok());
+        assert_eq!(alloc.alloc_layout(Layout::new::<u8>()), Err(OutOfMemory));
+        alloc.validate().unwrap();
+    }
+
+    #[test]
+    fn tiny_heap_has_no_blocks() {
+        let mut heap = Heap([0u8; 8]);
+        let alloc = FreeListAlloc::new(&mut heap.0[..WORD]);
+        assert_eq!((alloc.capacity(), alloc.free_bytes()), (0, 0));
+        assert_eq!(alloc.alloc_layout(Layout::new::<u8>()), Err(OutOfMemory));
+        alloc.validate().unwrap();
+    }
+
+    #[test]
+    fn realloc_in_place_and_moving() {
+        let mut heap = Heap([0u8; 256]);
+        let alloc = FreeListAlloc::new(&mut heap.0);
+        let small = Layout::new::<[u8; 16]>();
+        let large = Layout::new::<[u8; 64]>();
+        let ptr = alloc.alloc_layout(small).unwrap().cast::<u8>();
+        unsafe {
+            ptr.write_bytes(7, 16);
+            // grows into the free space behind it
+            let grown = alloc.realloc(ptr, small, large).unwrap();
+            assert_eq!(grown, ptr);
+            let blocker = alloc.alloc_layout(small).unwrap();
+            // shrinks in place, then has to move to grow past the blocker
+            let shrunk = alloc.realloc(grown, large, small).unwrap();
+            assert_eq!(shrunk, ptr);
+            alloc.validate().unwrap();
+            let moved = alloc.realloc(shrunk, small, Layout::new::<[u8; 96]>()).unwrap();
+            assert_ne!(moved, ptr);
+            assert_eq!(core::slice::from_raw_parts(moved.as_ptr(), 16), &[7; 16]);
+            alloc.validate().unwrap();
+            alloc.dealloc(blocker.cast(), small);
+            alloc.dealloc(moved, Layout::new::<[u8; 96]>());
+        }
+        assert_eq!(alloc.free_bytes(), alloc.capacity());
+    }
+
+    #[test]
+    fn validate_detects_corruption() {
+        let mut heap = Heap([0u8; 128]);
+        let alloc = FreeListAlloc::new(&mut heap.0);
+        alloc.alloc_layout(Layout::new::<u64>()).unwrap();
+        alloc.set_word(0, 3 * WORD + 4);
+        assert_eq!(alloc.validate(), Err("corrupted block size"));
+    }
+
+    fn random_workload(policy: FitPolicy, seed: u64) {
+        let mut heap = Heap([0u8; 4096]);
+        let alloc = FreeListAlloc::with_policy(&mut heap.0, policy);
+        let mut rng = Rng(seed);
+        let mut live: Vec<(NonNull<u8>, Layout, u8)> = Vec::new();
+
+        let fill = |ptr: NonNull<u8>, layout: Layout, tag: u8| unsafe { ptr.write_bytes(tag, layout.size()) };
+        let check = |ptr: NonNull<u8>, layout: Layout, tag: u8| unsafe {
+            assert!(core::slice::from_raw_parts(ptr.as_ptr(), layout.size()).iter().all(|&b| b == tag));
+        };
+
+        for step in 0..if cfg!(miri) { 200 } else { 5000 } {
+            let tag = step as u8;
+            let layout = Layout::from_size_align(rng.next(200), 1 << rng.next(6)).unwrap();
+            match rng.next(3) {
+                0 if !live.is_empty() => {
+                    let (ptr, old_layout, tag) = live.swap_remove(rng.next(live.len()));
+                    check(ptr, old_layout, tag);
+                    unsafe { alloc.dealloc(ptr, old_layout) };
+                }
+                1 if !live.is_empty() => {
+                    let index = rng.next(live.len());
+                    let (ptr, old_layout, old_tag) = live[index];
+                    check(ptr, old_layout, old_tag);
+                    if let Ok(new_ptr) = unsafe { alloc.realloc(ptr, old_layout, layout) } {
+                        assert!((new_ptr.as_ptr() as usize).is_multiple_of(layout.align()));
+                        let kept = Layout::from_size_align(old_layout.size().min(layout.size()), 1).unwrap();
+                        check(new_ptr, kept, old_tag);
+                        fill(new_ptr, layout, tag);
+                        live[index] = (new_ptr, layout, tag);
+                    } else {
+                        check(ptr, old_layout, old_tag);
+                    }
+                }
+                _ => if let Ok(ptr) = alloc.alloc_layout(layout) {
+                    let ptr = ptr.cast::<u8>();
+                    assert!((ptr.as_ptr() as usize).is_multiple_of(layout.align()));
+                    fill(ptr, layout, tag);
+                    live.push((ptr, layout, tag));
+                }
+            }
+            alloc.validate().unwrap();
+        }
+
+        for (ptr, layout, tag) in live {
+            check(ptr, layout, tag);
+            unsafe { alloc.dealloc(ptr, layout) };
+        }
+        alloc.validate().unwrap();
+        assert_eq!(alloc.free_bytes(), alloc.capacity());
+    }
+
+    #[test]
+    fn random_first_fit() {
+        for seed in 1..=4 {
+            random_workload(FitPolicy::FirstFit, seed);
+        }
+    }
+
+    #[test]
+    fn random_best_fit() {
+        for seed in 1..=4 {
+            random_workload(FitPolicy::BestFit, seed);
+        }
+    }
+
+    #[test]
+    fn typed_api() {
+        let mut heap = Heap([0u8; 128]);
+        let alloc = FreeListAlloc::new(&mut heap.0);
+        let slice = alloc.alloc_from_fn(4, |i| i as u32).unwrap();
+        assert_eq!(slice, &[0, 1, 2, 3]);
+        assert_eq!(alloc.alloc_str("free list").unwrap(), "free list");
+        alloc.validate().unwrap();
+    }
+}
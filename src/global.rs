@@ -0,0 +1,145 @@
+use core::alloc::{GlobalAlloc, Layout};
+use core::cell::UnsafeCell;
+use core::ptr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::align_padding;
+
+/// Bump allocator over a static heap, usable as `#[global_allocator]`.
+/// `dealloc` and `realloc` only reclaim or grow the most recent allocation.
+pub struct GlobalBumpAlloc<const N: usize> {
+    heap: UnsafeCell<[u8; N]>,
+    cursor: AtomicUsize,
+}
+
+unsafe impl<const N: usize> Sync for GlobalBumpAlloc<N> {}
+
+impl<const N: usize> GlobalBumpAlloc<N> {
+    pub const fn new() -> Self {
+        GlobalBumpAlloc { heap: UnsafeCell::new([0; N]), cursor: AtomicUsize::new(0) }
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn used(&self) -> usize {
+        self.cursor.load(Ordering::Relaxed)
+    }
+
+    fn heap_ptr(&self) -> *mut u8 {
+        self.heap.get().cast()
+    }
+
+    fn offset_of(&self, ptr: *mut u8) -> usize {
+        ptr as usize - self.heap_ptr() as usize
+    }
+
+    // Moves the cursor from `top` to `new_top` if nobody else bumped it in between
+    fn try_move_cursor(&self, top: usize, new_top: usize) -> bool {
+        self.cursor.compare_exchange(top, new_top, Ordering::AcqRel, Ordering::Relaxed).is_ok()
+    }
+}
+
+impl<const N: usize> Default for GlobalBumpAlloc<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl<const N: usize> GlobalAlloc for GlobalBumpAlloc<N> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut top = self.cursor.load(Ordering::Relaxed);
+        loop {
+            let waste_bytes = align_padding(self.heap_ptr() as usize + top, layout.align());
+            let new_top = match top.checked_add(waste_bytes).and_then(|start| start.checked_add(layout.size())) {
+                Some(new_top) if new_top <= N => new_top,
+                _ => return ptr::null_mut(),
+            };
+            match self.cursor.compare_exchange_weak(top, new_top, Ordering::AcqRel, Ordering::Relaxed) {
+                Ok(_) => return unsafe { self.heap_ptr().add(top + waste_bytes) },
+                Err(current) => top = current,
+            }
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let start = self.offset_of(ptr);
+        self.try_move_cursor(start + layout.size(), start);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let start = self.offset_of(ptr);
+        let top = start + layout.size();
+        if new_size <= N - start && self.try_move_cursor(top, start + new_size) {
+            return ptr;
+        }
+        if new_size <= layout.size() {
+            return ptr;
+        }
+
+        let new_layout = unsafe { Layout::from_size_align_unchecked(new_size, layout.align()) };
+        let new_ptr = unsafe { self.alloc(new_layout) };
+        if !new_ptr.is_null() {
+            unsafe {
+                ptr::copy_nonoverlapping(ptr, new_ptr, layout.size());
+                self.dealloc(ptr, layout);
+            }
+        }
+        new_ptr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn global_alloc_aligned() {
+        let heap = GlobalBumpAlloc::<256>::new();
+        for align in [1, 2, 4, 8, 16, 32] {
+            let layout = Layout::from_size_align(3, align).unwrap();
+            let ptr = unsafe { heap.alloc(layout) };
+            assert!(!ptr.is_null());
+            assert!((ptr as usize).is_multiple_of(align));
+        }
+    }
+
+    #[test]
+    fn global_alloc_out_of_mem() {
+        let heap = GlobalBumpAlloc::<16>::new();
+        let layout = Layout::from_size_align(16, 1).unwrap();
+        assert!(!unsafe { heap.alloc(layout) }.is_null());
+        assert!(unsafe { heap.alloc(Layout::new::<u8>()) }.is_null());
+        assert_eq!(heap.used(), 16);
+    }
+
+    #[test]
+    fn global_dealloc_rolls_back_last() {
+        let heap = GlobalBumpAlloc::<64>::new();
+        let layout = Layout::new::<u8>();
+        let first = unsafe { heap.alloc(layout) };
+        let second = unsafe { heap.alloc(layout) };
+        unsafe { heap.dealloc(first, layout) };
+        assert_eq!(heap.used(), 2);
+        unsafe { heap.dealloc(second, layout) };
+        assert_eq!(heap.used(), 1);
+    }
+
+    #[test]
+    fn global_realloc_grows_last_in_place() {
+        let heap = GlobalBumpAlloc::<64>::new();
+        let layout = Layout::from_size_align(4, 1).unwrap();
+        let first = unsafe { heap.alloc(layout) };
+        unsafe { first.write_bytes(7, 4) };
+        let grown = unsafe { heap.realloc(first, layout, 8) };
+        assert_eq!(grown, first);
+        assert_eq!(heap.used(), 8);
+
+        let _second = unsafe { heap.alloc(layout) };
+        let moved = unsafe { heap.realloc(first, Layout::from_size_align(8, 1).unwrap(), 16) };
+        assert_ne!(moved, first);
+        assert_eq!(unsafe { core::slice::from_raw_parts(moved, 4) }, &[7; 4]);
+        assert_eq!(heap.used(), 28);
+    }
+}
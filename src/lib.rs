@@ -0,0 +1,988 @@
+#![no_std]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+
+#[cfg(any(feature = "std", test))]
+extern crate std;
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+
+// Implements an `Allocator` trait for `&Alloc`, given the paths of the trait and its
+// error type. Only the most recent allocation can grow or shrink in place,
+// everything else is copied and deallocation is a no-op
+#[cfg(any(feature = "allocator_api", feature = "allocator-api2"))]
+macro_rules! impl_allocator {
+    ($allocator:path, $alloc_error:path) => {
+        unsafe impl $allocator for &$crate::Alloc<'_> {
+            fn allocate(&self, layout: core::alloc::Layout) -> Result<core::ptr::NonNull<[u8]>, $alloc_error> {
+                let ptr = self.reserve(layout).map_err(|_| $alloc_error)?;
+                Ok(core::ptr::NonNull::slice_from_raw_parts(ptr, layout.size()))
+            }
+
+            unsafe fn deallocate(&self, _ptr: core::ptr::NonNull<u8>, _layout: core::alloc::Layout) {}
+
+            unsafe fn grow(
+                &self,
+                ptr: core::ptr::NonNull<u8>,
+                old_layout: core::alloc::Layout,
+                new_layout: core::alloc::Layout,
+            ) -> Result<core::ptr::NonNull<[u8]>, $alloc_error> {
+                let new_ptr = unsafe { self.realloc_layout(ptr, old_layout, new_layout) }.map_err(|_| $alloc_error)?;
+                Ok(core::ptr::NonNull::slice_from_raw_parts(new_ptr, new_layout.size()))
+            }
+
+            unsafe fn grow_zeroed(
+                &self,
+                ptr: core::ptr::NonNull<u8>,
+                old_layout: core::alloc::Layout,
+                new_layout: core::alloc::Layout,
+            ) -> Result<core::ptr::NonNull<[u8]>, $alloc_error> {
+                let new_ptr = unsafe { self.realloc_layout(ptr, old_layout, new_layout) }.map_err(|_| $alloc_error)?;
+                unsafe {
+                    new_ptr.add(old_layout.size()).write_bytes(0, new_layout.size() - old_layout.size());
+                }
+                Ok(core::ptr::NonNull::slice_from_raw_parts(new_ptr, new_layout.size()))
+            }
+
+            unsafe fn shrink(
+                &self,
+                ptr: core::ptr::NonNull<u8>,
+                old_layout: core::alloc::Layout,
+                new_layout: core::alloc::Layout,
+            ) -> Result<core::ptr::NonNull<[u8]>, $alloc_error> {
+                let new_ptr = unsafe { self.realloc_layout(ptr, old_layout, new_layout) }.map_err(|_| $alloc_error)?;
+                Ok(core::ptr::NonNull::slice_from_raw_parts(new_ptr, new_layout.size()))
+            }
+        }
+    };
+}
+
+#[cfg(feature = "allocator_api")]
+mod allocator_api;
+#[cfg(feature = "allocator-api2")]
+mod api2;
+mod arena_allocator;
+mod arena_box;
+mod arena_string;
+mod arena_vec;
+mod buddy;
+#[cfg(feature = "alloc")]
+mod chunked;
+mod double_ended;
+mod drop_list;
+mod fallback;
+mod free_list;
+mod global;
+mod pool;
+mod resettable;
+mod stats;
+mod sync;
+#[cfg(feature = "std")]
+mod thread_arena;
+mod tlsf;
+
+pub use arena_allocator::ArenaAllocator;
+pub use arena_box::ArenaBox;
+pub use arena_string::ArenaString;
+pub use arena_vec::{ArenaVec, ArenaVecIntoIter};
+pub use buddy::BuddyAlloc;
+#[cfg(feature = "alloc")]
+pub use chunked::ChunkedAlloc;
+pub use double_ended::{BackCheckpoint, DoubleEndedAlloc, FrontCheckpoint};
+pub use fallback::{FallbackAlloc, TierStats};
+pub use free_list::{FitPolicy, FreeListAlloc};
+pub use global::GlobalBumpAlloc;
+pub use pool::{Pool, PoolBox};
+pub use resettable::ResettableAlloc;
+#[cfg(feature = "stats")]
+pub use stats::AllocStats;
+#[cfg(not(feature = "stats"))]
+use stats::AllocStats;
+pub use sync::SyncAlloc;
+#[cfg(feature = "std")]
+pub use thread_arena::{thread_arena_stats, with_thread_arena, ThreadArenaStats, THREAD_ARENA_SIZE};
+pub use tlsf::TlsfAlloc;
+
+pub struct Alloc<'mem> {
+    pub(crate) heap: *mut u8,
+    size: usize,
+    offset: Cell<usize>,
+    #[cfg(feature = "stats")]
+    stats: Cell<AllocStats>,
+    _mem: PhantomData<&'mem mut [u8]>,
+}
+
+unsafe impl Send for Alloc<'_> {}
+
+#[derive(Debug, PartialEq)]
+pub struct OutOfMemory;
+
+impl fmt::Display for OutOfMemory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("out of memory")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for OutOfMemory {}
+
+pub type AllocResult<T> = Result<T, OutOfMemory>;
+
+/// Bump cursor position saved by `Alloc::checkpoint`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub(crate) offset: usize,
+}
+
+impl<'mem> Alloc<'mem> {
+    pub fn new(heap: &'mem mut [u8]) -> Self {
+        Alloc {
+            heap: heap.as_mut_ptr(),
+            size: heap.len(),
+            offset: Cell::new(0),
+            #[cfg(feature = "stats")]
+            stats: Cell::new(AllocStats::default()),
+            _mem: PhantomData,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.size
+    }
+
+    pub fn used(&self) -> usize {
+        self.offset.get()
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.size - self.offset.get()
+    }
+
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> AllocStats {
+        AllocStats { bytes_used: self.used(), ..self.stats.get() }
+    }
+
+    /// Total alignment padding consumed, including padding released by resets.
+    #[cfg(feature = "stats")]
+    pub fn wasted_alignment_bytes(&self) -> usize {
+        self.stats.get().padding_bytes
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint { offset: self.offset.get() }
+    }
+
+    /// Releases everything allocated since `checkpoint` was taken.
+    ///
+    /// # Safety
+    ///
+    /// Nothing allocated after `checkpoint` may be used after the rewind.
+    pub unsafe fn rewind(&mut self, checkpoint: Checkpoint) {
+        debug_assert!(checkpoint.offset <= self.offset.get(), "rewind past the current cursor");
+        self.offset.set(checkpoint.offset);
+    }
+
+    /// Releases everything, making the whole original heap available again.
+    ///
+    /// # Safety
+    ///
+    /// Nothing allocated from this `Alloc` may be used after the reset.
+    /// `ResettableAlloc` offers a safe `reset` instead.
+    pub unsafe fn reset(&mut self) {
+        self.offset.set(0);
+    }
+
+    /// Runs `f` with a child allocator over the free space; everything it
+    /// allocates is released when `f` returns, since no reference can escape it.
+    pub fn scope<R>(&mut self, f: impl FnOnce(&mut Alloc<'_>) -> R) -> R {
+        let mut child = Alloc {
+            heap: self.top(),
+            size: self.remaining(),
+            offset: Cell::new(0),
+            #[cfg(feature = "stats")]
+            stats: Cell::new(AllocStats::default()),
+            _mem: PhantomData,
+        };
+        let result = f(&mut child);
+        #[cfg(feature = "stats")]
+        self.update_stats(|stats| stats.merge_child(self.offset.get(), child.stats.get()));
+        result
+    }
+
+    pub fn alloc<'item, T>(&self, item: T) -> AllocResult<&'item mut T>
+        where 'mem: 'item
+    {
+        Ok(unsafe { detach(ArenaAllocator::alloc(self, item)?) })
+    }
+
+    pub fn alloc_box<'item, T>(&self, item: T) -> AllocResult<ArenaBox<'item, T>>
+        where 'mem: 'item, T: 'item
+    {
+        Ok(unsafe { ArenaBox::from_mut(self.alloc(item)?) })
+    }
+
+    pub fn alloc_box_from_fn<'item, T>(&self, size: usize, f: impl FnMut(usize) -> T) -> AllocResult<ArenaBox<'item, [T]>>
+        where 'mem: 'item, T: 'item
+    {
+        Ok(unsafe { ArenaBox::from_mut(self.alloc_from_fn(size, f)?) })
+    }
+
+    pub fn alloc_from_fn<'item, T>(&self, size: usize, f: impl FnMut(usize) -> T) -> AllocResult<&'item mut [T]>
+        where 'mem: 'item
+    {
+        Ok(unsafe { detach(ArenaAllocator::alloc_from_fn(self, size, f)?) })
+    }
+
+    // Note: on error the reserved region is not returned to the heap,
+    // but every element already produced by `f` is dropped
+    pub fn try_alloc_from_fn<'item, T, E>(&self, size: usize, f: impl FnMut(usize) -> Result<T, E>) -> Result<&'item mut [T], E>
+        where 'mem: 'item, E: From<OutOfMemory>
+    {
+        Ok(unsafe { detach(ArenaAllocator::try_alloc_from_fn(self, size, f)?) })
+    }
+
+    pub fn alloc_slice_copy<'item, T: Copy>(&self, src: &[T]) -> AllocResult<&'item mut [T]>
+        where 'mem: 'item
+    {
+        Ok(unsafe { detach(ArenaAllocator::alloc_slice_copy(self, src)?) })
+    }
+
+    pub fn alloc_slice_clone<'item, T: Clone>(&self, src: &[T]) -> AllocResult<&'item mut [T]>
+        where 'mem: 'item
+    {
+        Ok(unsafe { detach(ArenaAllocator::alloc_slice_clone(self, src)?) })
+    }
+
+    pub fn alloc_slice_fill_with<'item, T>(&self, size: usize, f: impl FnMut(usize) -> T) -> AllocResult<&'item mut [T]>
+        where 'mem: 'item
+    {
+        self.alloc_from_fn(size, f)
+    }
+
+    // Note: panics if the iterator yields fewer items than its `len()`
+    pub fn alloc_slice_fill_iter<'item, T, I>(&self, iter: I) -> AllocResult<&'item mut [T]>
+        where 'mem: 'item, I: IntoIterator<Item = T>, I::IntoIter: ExactSizeIterator
+    {
+        Ok(unsafe { detach(ArenaAllocator::alloc_slice_fill_iter(self, iter)?) })
+    }
+
+    pub fn alloc_str<'item>(&self, src: &str) -> AllocResult<&'item mut str>
+        where 'mem: 'item
+    {
+        Ok(unsafe { detach(ArenaAllocator::alloc_str(self, src)?) })
+    }
+
+    pub fn alloc_fmt<'item>(&self, args: fmt::Arguments<'_>) -> AllocResult<&'item mut str>
+        where 'mem: 'item
+    {
+        Ok(unsafe { detach(ArenaAllocator::alloc_fmt(self, args)?) })
+    }
+
+    pub fn alloc_layout(&self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+        let ptr = self.reserve(layout)?;
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    pub fn alloc_zeroed_layout(&self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+        ArenaAllocator::alloc_zeroed_layout(self, layout)
+    }
+
+    pub fn alloc_uninit<'item, T>(&self) -> AllocResult<&'item mut MaybeUninit<T>>
+        where 'mem: 'item
+    {
+        Ok(unsafe { detach(ArenaAllocator::alloc_uninit(self)?) })
+    }
+
+    pub fn alloc_uninit_slice<'item, T>(&self, size: usize) -> AllocResult<&'item mut [MaybeUninit<T>]>
+        where 'mem: 'item
+    {
+        Ok(unsafe { detach(ArenaAllocator::alloc_uninit_slice(self, size)?) })
+    }
+
+    // Formats straight into the free space, so nothing is consumed on error.
+    // The free space is held while formatting: allocations made by `Display`
+    // impls of the arguments fail with `OutOfMemory`
+    pub(crate) fn format<'item>(&self, args: fmt::Arguments<'_>) -> AllocResult<&'item mut str>
+        where 'mem: 'item
+    {
+        let mut writer = HeapWriter { ptr: self.top(), capacity: self.remaining(), len: 0, overflow: false };
+        let result = {
+            let _hold = HoldFreeSpace { alloc: self, offset: self.offset.replace(self.size) };
+            fmt::write(&mut writer, args)
+        };
+        if result.is_err() {
+            if writer.overflow { return Err(OutOfMemory); }
+            panic!("a formatting trait implementation returned an error");
+        }
+
+        let str_ptr = self.alloc_mem(writer.len);
+        self.update_stats(|stats| stats.record_allocation(writer.len));
+        Ok(unsafe { core::str::from_utf8_unchecked_mut(core::slice::from_raw_parts_mut(str_ptr, writer.len)) })
+    }
+
+    // Consumes alignment padding plus `layout.size()` bytes, or nothing on error
+    pub(crate) fn reserve(&self, layout: Layout) -> AllocResult<NonNull<u8>> {
+        if !self.fits(layout) { return Err(OutOfMemory); }
+
+        let waste_bytes = self.calc_waste_bytes(layout.align());
+        self.alloc_mem(waste_bytes);
+        self.update_stats(|stats| {
+            stats.record_padding(waste_bytes);
+            stats.record_allocation(layout.size());
+        });
+        Ok(unsafe { NonNull::new_unchecked(self.alloc_mem(layout.size())) })
+    }
+
+    // Whether `layout` fits in the free space together with its alignment padding
+    pub(crate) fn fits(&self, layout: Layout) -> bool {
+        let waste_bytes = self.calc_waste_bytes(layout.align());
+        waste_bytes.checked_add(layout.size()).is_some_and(|total_size| total_size <= self.remaining())
+    }
+
+    // Grows or shrinks in place when `ptr` is the last allocation, copies otherwise
+    #[cfg(any(feature = "allocator_api", feature = "allocator-api2"))]
+    pub(crate) unsafe fn realloc_layout(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> AllocResult<NonNull<u8>> {
+        let aligned = (ptr.as_ptr() as usize).is_multiple_of(new_layout.align());
+        if aligned && self.resize_last(ptr, old_layout.size(), new_layout.size()) {
+            return Ok(ptr);
+        }
+        if aligned && new_layout.size() <= old_layout.size() {
+            return Ok(ptr);
+        }
+
+        let new_ptr = self.reserve(new_layout)?;
+        let copy_size = old_layout.size().min(new_layout.size());
+        unsafe { core::ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_ptr(), copy_size) };
+        Ok(new_ptr)
+    }
+
+    // Resizes the allocation at `ptr` if it is the last one and the new size fits
+    pub(crate) fn resize_last(&self, ptr: NonNull<u8>, old_size: usize, new_size: usize) -> bool {
+        if ptr.as_ptr() as usize + old_size != self.top() as usize { return false; }
+
+        let start = self.offset.get() - old_size;
+        if self.size - start < new_size { return false; }
+
+        self.offset.set(start + new_size);
+        self.update_stats(|stats| stats.record_resize(new_size, start + new_size));
+        true
+    }
+
+    fn alloc_mem(&self, size: usize) -> *mut u8 {
+        let item_ptr = self.top();
+        self.offset.set(self.offset.get() + size);
+        self.update_stats(|stats| stats.record_usage(self.offset.get()));
+        item_ptr
+    }
+
+    fn top(&self) -> *mut u8 {
+        unsafe { self.heap.add(self.offset.get()) }
+    }
+
+    fn calc_waste_bytes(&self, align: usize) -> usize {
+        align_padding(self.top() as usize, align)
+    }
+
+    // Compiles to nothing without the `stats` feature
+    #[inline(always)]
+    fn update_stats(&self, f: impl FnOnce(&mut AllocStats)) {
+        #[cfg(feature = "stats")]
+        {
+            let mut stats = self.stats.get();
+            f(&mut stats);
+            self.stats.set(stats);
+        }
+        #[cfg(not(feature = "stats"))]
+        let _ = f;
+    }
+}
+
+// Extends a reference from the `&self` borrow the trait ties it to
+// to the lifetime of the heap it was carved from.
+// Safety: `item` must point into a heap borrowed for `'mem` that is never handed
+// out again while `'item` lasts, with `'mem: 'item`
+unsafe fn detach<'item, T: ?Sized>(item: &mut T) -> &'item mut T {
+    unsafe { &mut *(item as *mut T) }
+}
+
+// Puts the cursor back after `alloc_fmt`, also when a formatting impl panics
+struct HoldFreeSpace<'a, 'mem> {
+    alloc: &'a Alloc<'mem>,
+    offset: usize,
+}
+
+impl Drop for HoldFreeSpace<'_, '_> {
+    fn drop(&mut self) {
+        self.alloc.offset.set(self.offset);
+    }
+}
+
+// Bytes to skip from `addr` to reach the next multiple of `align`
+fn align_padding(addr: usize, align: usize) -> usize {
+    (align - addr % align) % align
+}
+
+// Writes formatted output into unreserved heap bytes
+pub(crate) struct HeapWriter {
+    pub(crate) ptr: *mut u8,
+    pub(crate) capacity: usize,
+    pub(crate) len: usize,
+    pub(crate) overflow: bool,
+}
+
+impl fmt::Write for HeapWriter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.capacity - self.len < s.len() {
+            self.overflow = true;
+            return Err(fmt::Error);
+        }
+        unsafe { core::ptr::copy_nonoverlapping(s.as_ptr(), self.ptr.add(self.len), s.len()) };
+        self.len += s.len();
+        Ok(())
+    }
+}
+
+// Measures formatted output
+pub(crate) struct ByteCounter(pub(crate) usize);
+
+impl fmt::Write for ByteCounter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
+// Drops the initialized prefix of a slice under construction
+struct PartialSlice<T> {
+    ptr: *mut T,
+    len: usize,
+}
+
+impl<T> Drop for PartialSlice<T> {
+    fn drop(&mut self) {
+        unsafe { core::ptr::drop_in_place(core::ptr::slice_from_raw_parts_mut(self.ptr, self.len)) }
+    }
+}
+
+// Writes `f(0..size)` to `arr_ptr`, dropping the written prefix if `f` fails or panics.
+// Safety: `arr_ptr` must be aligned and valid for writes of `size` elements for `'item`
+pub(crate) unsafe fn fill_slice<'item, T, E>(arr_ptr: *mut T, size: usize, mut f: impl FnMut(usize) -> Result<T, E>) -> Result<&'item mut [T], E> {
+    let mut guard = PartialSlice { ptr: arr_ptr, len: 0 };
+    while guard.len < size {
+        let item = f(guard.len)?;
+        unsafe { arr_ptr.add(guard.len).write(item) };
+        guard.len += 1;
+    }
+    core::mem::forget(guard);
+
+    Ok(unsafe { core::slice::from_raw_parts_mut(arr_ptr, size) })
+}
+
+// Fixtures shared by the tests of every module
+#[cfg(test)]
+mod test_util {
+    use core::cell::Cell;
+
+    // Heap aligned for every primitive type
+    #[repr(align(16))]
+    pub(crate) struct Heap<const N: usize>(pub(crate) [u8; N]);
+
+    // Heap aligned to a page, for over-aligned types and exact layouts
+    #[repr(C, align(4096))]
+    pub(crate) struct Page<const N: usize>(pub(crate) [u8; N]);
+
+    impl<const N: usize> Page<N> {
+        pub(crate) fn new() -> Self {
+            Page([0; N])
+        }
+    }
+
+    // Counts how many times a value was dropped
+    pub(crate) struct DropCounter<'a>(pub(crate) &'a Cell<usize>);
+
+    impl Drop for DropCounter<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    // xorshift64, enough to drive the randomized tests
+    pub(crate) struct Rng(pub(crate) u64);
+
+    impl Rng {
+        pub(crate) fn next(&mut self, bound: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % bound as u64) as usize
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{DropCounter, Page};
+    use std::string::String;
+    use std::vec::Vec;
+
+    #[test]
+    fn alloc_bytes() {
+        let mut heap: [u8; 4] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let _ = alloc.alloc::<u8>(1);
+        let _ = alloc.alloc::<u8>(2);
+        let _ = alloc.alloc::<u8>(3);
+        assert_eq!(heap, [1, 2, 3, 0])
+    }
+
+    #[test]
+    fn alloc_i64s() {
+        let mut page = Page::<32>::new();
+        let alloc = Alloc::new(&mut page.0);
+        let _ = alloc.alloc::<i64>(1);
+        let _ = alloc.alloc::<i64>(2);
+        let _ = alloc.alloc::<i64>(3);
+        assert_eq!(page.0, [
+            1, 0, 0, 0, 0, 0, 0, 0,
+            2, 0, 0, 0, 0, 0, 0, 0,
+            3, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0,
+        ])
+    }
+
+    #[test]
+    fn alloc_aligned() {
+        let mut page = Page::<16>::new();
+        let alloc = Alloc::new(&mut page.0);
+
+        let u8_ref = alloc.alloc::<u8>(1).unwrap();
+
+        assert!((u8_ref as *mut u8 as usize).is_multiple_of(2));
+
+        let _ = alloc.alloc::<u16>(2);
+        let _ = alloc.alloc::<u16>(3);
+
+        assert_eq!(page.0, [
+            1, 0, 2, 0,
+            3, 0, 0, 0,
+            0, 0, 0, 0,
+            0, 0, 0, 0,
+        ])
+    }
+
+    #[test]
+    fn alloc_out_of_mem() {
+        let mut page = Page::<4>::new();
+        let alloc = Alloc::new(&mut page.0);
+
+        let u8_ref = alloc.alloc::<u8>(1).unwrap();
+
+        assert!((u8_ref as *mut u8 as usize).is_multiple_of(2));
+
+        let result = alloc.alloc::<u32>(2);
+
+        assert_eq!(result, Err(OutOfMemory))
+    }
+
+    #[test]
+    fn alloc_fn() {
+        let mut heap: [u8; 8] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let _ = alloc.alloc_from_fn::<u8>(4, |i| (i + 1) as u8);
+        assert_eq!(heap, [1, 2, 3, 4, 0, 0, 0, 0])
+    }
+
+    #[repr(align(16))]
+    struct Align16(#[allow(dead_code)] u8);
+
+    #[repr(align(64))]
+    struct Align64(#[allow(dead_code)] u8);
+
+    #[repr(align(256))]
+    struct Align256(#[allow(dead_code)] u8);
+
+    #[repr(align(4096))]
+    struct Align4096(#[allow(dead_code)] u8);
+
+    #[repr(align(32))]
+    struct ZstAlign32;
+
+    struct Ranges {
+        heap_start: usize,
+        heap_end: usize,
+        items: Vec<(usize, usize)>,
+    }
+
+    impl Ranges {
+        fn new(heap: &[u8]) -> Self {
+            let heap_start = heap.as_ptr() as usize;
+            Ranges { heap_start, heap_end: heap_start + heap.len(), items: Vec::new() }
+        }
+
+        fn check<T>(&mut self, item: &mut T) {
+            let addr = item as *mut T as usize;
+            let size = core::mem::size_of::<T>();
+            assert_eq!(addr % core::mem::align_of::<T>(), 0, "misaligned {}", core::any::type_name::<T>());
+            assert!(addr >= self.heap_start && addr + size <= self.heap_end);
+            if size == 0 { return; }
+            for &(start, len) in &self.items {
+                assert!(addr + size <= start || start + len <= addr, "overlapping {}", core::any::type_name::<T>());
+            }
+            self.items.push((addr, size));
+        }
+    }
+
+    macro_rules! alloc_checked {
+        ($alloc:ident, $ranges:ident, $($value:expr),+ $(,)?) => {
+            $(
+                if let Ok(item) = $alloc.alloc($value) {
+                    $ranges.check(item);
+                }
+            )+
+        };
+    }
+
+    #[test]
+    fn alloc_primitives_aligned_from_every_offset() {
+        let mut page = Page::<256>::new();
+        for offset in 0..64 {
+            let heap = &mut page.0[offset..];
+            let mut ranges = Ranges::new(heap);
+            let alloc = Alloc::new(heap);
+            for _ in 0..4 {
+                alloc_checked!(alloc, ranges,
+                    1u8, 1u16, 1u32, 1u64, 1u128, 1usize,
+                    1i8, 1i16, 1i32, 1i64, 1i128, 1isize,
+                    1f32, 1f64, true, 'x', (), [0u8; 0],
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn alloc_over_aligned_from_every_offset() {
+        let mut page = Page::<{ 3 * 4096 }>::new();
+        for offset in 0..=128 {
+            let heap = &mut page.0[offset..];
+            let mut ranges = Ranges::new(heap);
+            let alloc = Alloc::new(heap);
+            alloc_checked!(alloc, ranges,
+                1u8, Align16(1), 1u8, Align64(1), 1u16, Align256(1),
+                1u8, Align4096(1), 1u8, Align16(1),
+            );
+        }
+    }
+
+    #[test]
+    fn alloc_zst_aligned_from_every_offset() {
+        let mut page = Page::<128>::new();
+        for offset in 0..64 {
+            let heap = &mut page.0[offset..];
+            let mut ranges = Ranges::new(heap);
+            let alloc = Alloc::new(heap);
+            alloc_checked!(alloc, ranges, 1u8, ZstAlign32, 1u8, (), ZstAlign32, 1u64);
+        }
+    }
+
+    #[test]
+    fn alloc_fn_aligned_from_every_offset() {
+        let mut page = Page::<256>::new();
+        for offset in 0..32 {
+            let heap = &mut page.0[offset..];
+            let heap_end = heap.as_ptr() as usize + heap.len();
+            let alloc = Alloc::new(heap);
+            let _ = alloc.alloc(1u8).unwrap();
+            let slice = alloc.alloc_from_fn::<u64>(4, |i| i as u64).unwrap();
+            assert_eq!(slice.as_ptr() as usize % core::mem::align_of::<u64>(), 0);
+            assert!(slice.as_ptr() as usize + core::mem::size_of_val(slice) <= heap_end);
+            assert_eq!(slice, &[0, 1, 2, 3]);
+        }
+    }
+
+    #[test]
+    fn alloc_padding_is_distance_to_next_aligned_address() {
+        let mut page = Page::<64>::new();
+        let alloc = Alloc::new(&mut page.0[1..]);
+        assert_eq!(alloc.calc_waste_bytes(core::mem::align_of::<u32>()), 3);
+        assert_eq!(alloc.calc_waste_bytes(core::mem::align_of::<u64>()), 7);
+        assert_eq!(alloc.calc_waste_bytes(core::mem::align_of::<u8>()), 0);
+        let alloc = Alloc::new(&mut page.0[8..]);
+        assert_eq!(alloc.calc_waste_bytes(core::mem::align_of::<u64>()), 0);
+        assert_eq!(alloc.calc_waste_bytes(core::mem::align_of::<u128>()), 8);
+    }
+
+    #[test]
+    fn alloc_fn_out_of_mem_before_calling_f() {
+        let mut heap: [u8; 8] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let mut calls = 0;
+        let result = alloc.alloc_from_fn::<u8>(9, |i| { calls += 1; i as u8 });
+        assert_eq!(result, Err(OutOfMemory));
+        assert_eq!(calls, 0);
+        assert_eq!(alloc.alloc_from_fn::<u8>(8, |i| i as u8).unwrap(), &[0, 1, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn alloc_fn_size_overflow() {
+        let mut heap: [u8; 8] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        assert_eq!(alloc.alloc_from_fn::<u64>(usize::MAX, |_| unreachable!()), Err(OutOfMemory));
+        assert_eq!(alloc.alloc_from_fn::<u16>(usize::MAX / 2 + 1, |_| unreachable!()), Err(OutOfMemory));
+    }
+
+    #[test]
+    fn alloc_fn_zst() {
+        let mut heap: [u8; 0] = [];
+        let alloc = Alloc::new(&mut heap);
+        assert_eq!(alloc.alloc_from_fn(1000, |_| ()).unwrap().len(), 1000);
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum BuildError {
+        OutOfMemory,
+        Rejected(usize),
+    }
+
+    impl From<OutOfMemory> for BuildError {
+        fn from(_: OutOfMemory) -> Self {
+            BuildError::OutOfMemory
+        }
+    }
+
+    #[test]
+    fn try_alloc_fn() {
+        let mut heap: [u8; 8] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let slice = alloc.try_alloc_from_fn::<u16, BuildError>(3, |i| Ok(i as u16 * 2)).unwrap();
+        assert_eq!(slice, &[0, 2, 4]);
+        let result = alloc.try_alloc_from_fn::<u32, BuildError>(2, |i| Ok(i as u32));
+        assert_eq!(result, Err(BuildError::OutOfMemory));
+    }
+
+    #[test]
+    fn try_alloc_fn_drops_partial_on_error() {
+        let drops = core::cell::Cell::new(0);
+        let mut heap: [u8; 128] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let result = alloc.try_alloc_from_fn(5, |i| {
+            if i == 3 { Err(BuildError::Rejected(i)) } else { Ok(DropCounter(&drops)) }
+        });
+        assert!(matches!(result, Err(BuildError::Rejected(3))));
+        assert_eq!(drops.get(), 3);
+    }
+
+    #[test]
+    fn alloc_layout_aligned() {
+        let mut page = Page::<64>::new();
+        let heap_start = page.0.as_ptr() as usize;
+        let alloc = Alloc::new(&mut page.0[1..]);
+        let bytes = alloc.alloc_layout(Layout::from_size_align(3, 1).unwrap()).unwrap();
+        assert_eq!(bytes.as_ptr() as *mut u8 as usize, heap_start + 1);
+        assert_eq!(bytes.len(), 3);
+        let block = alloc.alloc_layout(Layout::from_size_align(16, 16).unwrap()).unwrap();
+        assert_eq!(block.as_ptr() as *mut u8 as usize, heap_start + 16);
+        assert_eq!(alloc.alloc_layout(Layout::from_size_align(33, 1).unwrap()), Err(OutOfMemory));
+    }
+
+    #[test]
+    fn alloc_zeroed_layout() {
+        let mut heap: [u8; 8] = core::array::from_fn(|_| 0xFF);
+        let alloc = Alloc::new(&mut heap);
+        let _ = alloc.alloc::<u8>(1);
+        let _ = alloc.alloc_zeroed_layout(Layout::new::<[u8; 4]>());
+        assert_eq!(heap, [1, 0, 0, 0, 0, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn alloc_uninit() {
+        let mut heap: [u8; 16] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let _ = alloc.alloc::<u8>(1);
+        let value = alloc.alloc_uninit::<u32>().unwrap();
+        assert!((value.as_ptr() as usize).is_multiple_of(core::mem::align_of::<u32>()));
+        assert_eq!(*value.write(0x0403_0201), 0x0403_0201);
+    }
+
+    #[test]
+    fn alloc_uninit_slice() {
+        let mut page = Page::<16>::new();
+        let heap = &mut page.0;
+        let alloc = Alloc::new(heap);
+        let slice = alloc.alloc_uninit_slice::<u16>(4).unwrap();
+        assert_eq!(slice.len(), 4);
+        for (i, item) in slice.iter_mut().enumerate() {
+            item.write(i as u16);
+        }
+        assert_eq!(alloc.alloc_uninit_slice::<u16>(5).err(), Some(OutOfMemory));
+        assert_eq!(alloc.alloc_uninit_slice::<u64>(usize::MAX).err(), Some(OutOfMemory));
+        assert_eq!(page.0[..8], [0, 0, 1, 0, 2, 0, 3, 0]);
+    }
+
+    #[test]
+    fn alloc_slice_copy() {
+        let mut page = Page::<16>::new();
+        let heap = &mut page.0;
+        let alloc = Alloc::new(heap);
+        let _ = alloc.alloc::<u8>(1);
+        let slice = alloc.alloc_slice_copy(&[1u16, 2, 3]).unwrap();
+        assert_eq!(slice, &[1, 2, 3]);
+        assert_eq!(alloc.alloc_slice_copy(&[0u32; 3]), Err(OutOfMemory));
+        assert_eq!(page.0[..8], [1, 0, 1, 0, 2, 0, 3, 0]);
+    }
+
+    #[test]
+    fn alloc_slice_clone() {
+        let src = [String::from("a"), String::from("bc")];
+        let mut heap: [u8; 128] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let cloned = alloc.alloc_slice_clone(&src).unwrap();
+        cloned[1].push('d');
+        assert_eq!(cloned, &["a", "bcd"]);
+        assert_eq!(src, ["a", "bc"]);
+        unsafe { core::ptr::drop_in_place(cloned) };
+    }
+
+    #[test]
+    fn alloc_slice_fill() {
+        let mut page = Page::<32>::new();
+        let alloc = Alloc::new(&mut page.0);
+        assert_eq!(alloc.alloc_slice_fill_with(3, |i| i * 10).unwrap(), &[0, 10, 20]);
+        assert_eq!(alloc.alloc_slice_fill_iter([7u8, 8, 9].iter().copied()).unwrap(), &[7, 8, 9]);
+        assert_eq!(alloc.alloc_slice_fill_iter(0..100u32), Err(OutOfMemory));
+    }
+
+    #[test]
+    fn alloc_str() {
+        let mut heap: [u8; 8] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let s = alloc.alloc_str("héllo").unwrap();
+        s.make_ascii_uppercase();
+        assert_eq!(s, "HéLLO");
+        assert_eq!(alloc.alloc_str("abc"), Err(OutOfMemory));
+    }
+
+    #[test]
+    fn alloc_fmt() {
+        let mut heap: [u8; 16] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        let s = alloc.alloc_fmt(format_args!("{}-{:02}", "id", 7)).unwrap();
+        assert_eq!(s, "id-07");
+        assert_eq!(alloc.alloc_fmt(format_args!("{:>12}", 1)), Err(OutOfMemory));
+        assert_eq!(alloc.alloc_fmt(format_args!("{:>11}", 1)).unwrap().len(), 11);
+    }
+
+    #[test]
+    fn rewind_reuses_memory() {
+        let mut heap: [u8; 8] = core::array::from_fn(|_| 0);
+        let mut alloc = Alloc::new(&mut heap);
+        let kept = alloc.alloc::<u8>(1).unwrap() as *mut u8;
+        let checkpoint = alloc.checkpoint();
+        let first = alloc.alloc_slice_copy(&[2u8; 7]).unwrap().as_ptr();
+        assert_eq!(alloc.alloc::<u8>(3), Err(OutOfMemory));
+        unsafe { alloc.rewind(checkpoint) };
+        let second = alloc.alloc_slice_copy(&[4u8; 7]).unwrap().as_ptr();
+        assert_eq!(first, second);
+        assert_eq!(unsafe { *kept }, 1);
+        assert_eq!(heap, [1, 4, 4, 4, 4, 4, 4, 4]);
+    }
+
+    #[test]
+    fn scope_reuses_memory() {
+        let mut page = Page::<16>::new();
+        let heap = &mut page.0;
+        let mut alloc = Alloc::new(heap);
+        let kept = alloc.alloc(1u32).unwrap();
+        let first = alloc.scope(|a| a.alloc_slice_copy(&[2u8; 12]).unwrap().as_ptr());
+        let second = alloc.scope(|a| {
+            let scratch = a.alloc_slice_copy(&[3u8; 12]).unwrap();
+            assert_eq!(a.alloc::<u8>(0), Err(OutOfMemory));
+            scratch.as_ptr()
+        });
+        assert_eq!(first, second);
+        assert_eq!(*kept, 1);
+        let after = alloc.alloc_slice_copy(&[4u8; 12]).unwrap();
+        assert_eq!(after.as_ptr(), first);
+    }
+
+    #[test]
+    fn scope_nested() {
+        let mut page = Page::<16>::new();
+        let heap = &mut page.0;
+        let mut alloc = Alloc::new(heap);
+        alloc.scope(|outer| {
+            let a = outer.alloc(1u64).unwrap();
+            let inner = outer.scope(|inner| inner.alloc(2u64).unwrap() as *mut u64);
+            let b = outer.alloc(3u64).unwrap();
+            assert_eq!(inner, b as *mut u64);
+            assert_eq!((*a, *b), (1, 3));
+        });
+        assert_eq!(alloc.checkpoint(), Checkpoint { offset: 0 });
+    }
+
+    #[test]
+    fn alloc_fmt_holds_free_space() {
+        struct Nested<'a, 'mem>(&'a Alloc<'mem>);
+
+        impl fmt::Display for Nested<'_, '_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                assert_eq!(self.0.alloc(1u8), Err(OutOfMemory));
+                f.write_str("nested")
+            }
+        }
+
+        let mut heap: [u8; 16] = core::array::from_fn(|_| 0);
+        let alloc = Alloc::new(&mut heap);
+        assert_eq!(alloc.alloc_fmt(format_args!("<{}>", Nested(&alloc))).unwrap(), "<nested>");
+        assert_eq!(alloc.alloc_str("12345678").unwrap(), "12345678");
+    }
+
+    #[test]
+    fn alloc_fmt_panic_releases_free_space() {
+        struct Panics;
+
+        impl fmt::Display for Panics {
+            fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+                panic!("formatting failed")
+            }
+        }
+
+        let mut page = Page::<16>::new();
+        let alloc = Alloc::new(&mut page.0);
+        alloc.alloc(1u8).unwrap();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| alloc.alloc_fmt(format_args!("{}", Panics))));
+        assert!(result.is_err());
+        assert_eq!(alloc.used(), 1);
+        assert_eq!(alloc.alloc_str("still fits").unwrap(), "still fits");
+    }
+
+    #[test]
+    fn reset_reuses_whole_heap() {
+        let mut page = Page::<4>::new();
+        let heap = &mut page.0;
+        let mut alloc = Alloc::new(heap);
+        let first = alloc.alloc(1u32).unwrap() as *mut u32;
+        unsafe { alloc.reset() };
+        let second = alloc.alloc(2u32).unwrap() as *mut u32;
+        assert_eq!(first, second);
+        assert_eq!(page.0, 2u32.to_ne_bytes());
+    }
+
+    #[test]
+    fn usage_counters() {
+        let mut page = Page::<16>::new();
+        let heap = &mut page.0;
+        let alloc = Alloc::new(heap);
+        assert_eq!((alloc.capacity(), alloc.used(), alloc.remaining()), (16, 0, 16));
+        alloc.alloc(1u8).unwrap();
+        alloc.alloc(2u32).unwrap();
+        assert_eq!((alloc.capacity(), alloc.used(), alloc.remaining()), (16, 8, 8));
+    }
+}
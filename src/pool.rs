@@ -0,0 +1,197 @@
+use core::cell::Cell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+
+use crate::{Alloc, AllocResult, OutOfMemory};
+
+// An unused slot holds the index of the next unused one; `capacity` ends the list
+union Slot<T> {
+    next: usize,
+    item: ManuallyDrop<T>,
+}
+
+/// Fixed number of `T` slots carved from an `Alloc`. Slots are handed out
+/// as `PoolBox`es and return to the pool when those are dropped.
+pub struct Pool<'a, T> {
+    slots: NonNull<Slot<T>>,
+    capacity: usize,
+    free: Cell<usize>,
+    in_use: Cell<usize>,
+    _mem: PhantomData<&'a mut [Slot<T>]>,
+}
+
+impl<'a, T> Pool<'a, T> {
+    pub fn with_capacity_in(capacity: usize, alloc: &'a Alloc<'_>) -> AllocResult<Self> {
+        let slots = alloc.alloc_from_fn(capacity, |i| Slot::<T> { next: i + 1 })?;
+        Ok(Pool {
+            slots: NonNull::from(slots).cast(),
+            capacity,
+            free: Cell::new(0),
+            in_use: Cell::new(0),
+            _mem: PhantomData,
+        })
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn in_use(&self) -> usize {
+        self.in_use.get()
+    }
+
+    pub fn available(&self) -> usize {
+        self.capacity - self.in_use.get()
+    }
+
+    pub fn alloc(&self, item: T) -> AllocResult<PoolBox<'_, T>> {
+        let index = self.free.get();
+        if index == self.capacity { return Err(OutOfMemory); }
+
+        let slot = unsafe { self.slots.add(index) };
+        unsafe {
+            self.free.set(slot.as_ref().next);
+            slot.write(Slot { item: ManuallyDrop::new(item) });
+        }
+        self.in_use.set(self.in_use.get() + 1);
+        Ok(PoolBox { pool: self, ptr: slot.cast() })
+    }
+
+    // Safety: the slot at `ptr` must be in use and its item already moved out or dropped
+    unsafe fn release(&self, ptr: NonNull<T>) {
+        let slot = ptr.cast::<Slot<T>>();
+        let index = unsafe { slot.offset_from(self.slots) } as usize;
+        unsafe { slot.write(Slot { next: self.free.get() }) };
+        self.free.set(index);
+        self.in_use.set(self.in_use.get() - 1);
+    }
+}
+
+/// Pool slot holding a `T`: dropping it runs the destructor and frees the slot.
+pub struct PoolBox<'p, T> {
+    pool: &'p Pool<'p, T>,
+    ptr: NonNull<T>,
+}
+
+impl<T> PoolBox<'_, T> {
+    /// Moves the value out, freeing the slot.
+    pub fn into_inner(this: Self) -> T {
+        let this = ManuallyDrop::new(this);
+        unsafe {
+            let item = this.ptr.read();
+            this.pool.release(this.ptr);
+            item
+        }
+    }
+}
+
+impl<T> Drop for PoolBox<'_, T> {
+    fn drop(&mut self) {
+        unsafe {
+            self.ptr.drop_in_place();
+            self.pool.release(self.ptr);
+        }
+    }
+}
+
+impl<T> Deref for PoolBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T> DerefMut for PoolBox<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for PoolBox<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Heap;
+    use std::rc::Rc;
+    use std::vec::Vec;
+
+    #[test]
+    fn alloc_until_exhausted() {
+        let mut heap = Heap([0u8; 64]);
+        let alloc = Alloc::new(&mut heap.0);
+        let pool = Pool::with_capacity_in(4, &alloc).unwrap();
+        let items: Vec<_> = (0..4u64).map(|i| pool.alloc(i).unwrap()).collect();
+        assert!(matches!(pool.alloc(4), Err(OutOfMemory)));
+        assert_eq!(items.iter().map(|item| **item).collect::<Vec<_>>(), [0, 1, 2, 3]);
+        assert_eq!((pool.capacity(), pool.in_use(), pool.available()), (4, 4, 0));
+    }
+
+    #[test]
+    fn drop_returns_slot() {
+        let mut heap = Heap([0u8; 64]);
+        let alloc = Alloc::new(&mut heap.0);
+        let pool = Pool::with_capacity_in(2, &alloc).unwrap();
+        let first = pool.alloc(1u32).unwrap();
+        let first_ptr = &*first as *const u32;
+        let _second = pool.alloc(2u32).unwrap();
+        drop(first);
+        assert_eq!(pool.in_use(), 1);
+        let mut third = pool.alloc(3u32).unwrap();
+        *third += 1;
+        assert_eq!((&*third as *const u32, *third), (first_ptr, 4));
+    }
+
+    #[test]
+    fn runs_destructors_and_into_inner() {
+        let mut heap = Heap([0u8; 64]);
+        let alloc = Alloc::new(&mut heap.0);
+        let pool = Pool::with_capacity_in(2, &alloc).unwrap();
+        let counter = Rc::new(());
+        let kept = pool.alloc(counter.clone()).unwrap();
+        drop(pool.alloc(counter.clone()).unwrap());
+        assert_eq!(Rc::strong_count(&counter), 2);
+        let moved = PoolBox::into_inner(kept);
+        assert_eq!((Rc::strong_count(&counter), pool.in_use()), (2, 0));
+        drop(moved);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn slots_come_from_alloc() {
+        let mut heap = Heap([0u8; 32]);
+        let alloc = Alloc::new(&mut heap.0);
+        assert!(Pool::<u64>::with_capacity_in(5, &alloc).is_err());
+        let pool = Pool::<u64>::with_capacity_in(4, &alloc).unwrap();
+        assert_eq!(alloc.remaining(), 0);
+        assert_eq!(*pool.alloc(7).unwrap(), 7);
+    }
+
+    #[test]
+    fn reuse_after_churn() {
+        let mut heap = Heap([0u8; 256]);
+        let alloc = Alloc::new(&mut heap.0);
+        let pool = Pool::with_capacity_in(8, &alloc).unwrap();
+        let mut live = Vec::new();
+        for i in 0..100usize {
+            if i % 3 == 2 {
+                live.swap_remove(i % live.len());
+            } else if let Ok(item) = pool.alloc(i) {
+                live.push(item);
+            }
+            assert_eq!(pool.in_use(), live.len());
+        }
+        let mut values: Vec<_> = live.iter().map(|item| **item).collect();
+        values.sort();
+        values.dedup();
+        assert_eq!(values.len(), live.len());
+    }
+}
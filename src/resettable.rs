@@ -0,0 +1,278 @@
+use core::alloc::Layout;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+
+use crate::drop_list::{DropList, Tracked, TrackedSlice};
+use crate::{fill_slice, Alloc, AllocResult, Checkpoint, OutOfMemory};
+
+/// Bump allocator whose allocations borrow the allocator itself, so that
+/// `reset` can safely hand the whole heap out again: it needs `&mut self`,
+/// which is only available once every allocated reference is gone.
+///
+/// Values placed with `alloc`, `alloc_from_fn`, `try_alloc_from_fn` and
+/// `alloc_slice_clone` are dropped in reverse allocation order when the
+/// allocator is reset, rewound past them or dropped; `alloc_no_drop` skips that.
+///
+/// ```compile_fail
+/// let mut heap = [0u8; 8];
+/// let mut alloc = simple_allocator::ResettableAlloc::new(&mut heap);
+/// let value = alloc.alloc(1u32).unwrap();
+/// alloc.reset();
+/// *value += 1;
+/// ```
+pub struct ResettableAlloc<'mem> {
+    alloc: Alloc<'mem>,
+    drops: DropList,
+}
+
+impl Drop for ResettableAlloc<'_> {
+    fn drop(&mut self) {
+        unsafe { self.drops.run_from(self.alloc.heap) }
+    }
+}
+
+// Every method reborrows the `'mem` reference returned by `Alloc` for `&self`
+#[allow(clippy::mut_from_ref)]
+impl<'mem> ResettableAlloc<'mem> {
+    pub fn new(heap: &'mem mut [u8]) -> Self {
+        ResettableAlloc { alloc: Alloc::new(heap), drops: DropList::new() }
+    }
+
+    pub fn reset(&mut self) {
+        unsafe {
+            self.drops.run_from(self.alloc.heap);
+            self.alloc.reset();
+        }
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        self.alloc.checkpoint()
+    }
+
+    /// Drops and releases everything allocated since `checkpoint`.
+    ///
+    /// Panics if `checkpoint` lies above the cursor, as a checkpoint taken from
+    /// another allocator or before an earlier rewind can.
+    pub fn rewind(&mut self, checkpoint: Checkpoint) {
+        assert!(checkpoint.offset <= self.alloc.used(), "checkpoint is past the current cursor");
+        unsafe {
+            self.drops.run_from(self.alloc.heap.add(checkpoint.offset));
+            self.alloc.rewind(checkpoint);
+        }
+    }
+
+    // Types without drop glue skip the drop list entirely
+    pub fn alloc<T: 'mem>(&self, item: T) -> AllocResult<&mut T> {
+        if !core::mem::needs_drop::<T>() { return self.alloc.alloc(item); }
+
+        let tracked = self.alloc.reserve(Layout::new::<Tracked<T>>())?.cast::<Tracked<T>>();
+        unsafe {
+            tracked.write(Tracked::new(item));
+            self.drops.push(tracked);
+            Ok(&mut *core::ptr::addr_of_mut!((*tracked.as_ptr()).item))
+        }
+    }
+
+    pub fn alloc_no_drop<T>(&self, item: T) -> AllocResult<&mut T> {
+        self.alloc.alloc(item)
+    }
+
+    pub fn alloc_from_fn<T: 'mem>(&self, size: usize, mut f: impl FnMut(usize) -> T) -> AllocResult<&mut [T]> {
+        self.try_alloc_from_fn(size, |i| Ok(f(i)))
+    }
+
+    // A slice with drop glue gets one drop list entry in front of its elements.
+    // The entry is pushed before `f` runs, keeping the list in address order,
+    // and only covers the elements once all of them are written
+    pub fn try_alloc_from_fn<T: 'mem, E>(&self, size: usize, f: impl FnMut(usize) -> Result<T, E>) -> Result<&mut [T], E>
+        where E: From<OutOfMemory>
+    {
+        if !core::mem::needs_drop::<T>() { return self.alloc.try_alloc_from_fn(size, f); }
+
+        let layout = TrackedSlice::layout::<T>(size).ok_or(OutOfMemory)?;
+        let header = self.alloc.reserve(layout)?.cast::<TrackedSlice>();
+        unsafe {
+            header.write(TrackedSlice::new::<T>());
+            self.drops.push_slice(header);
+            let items = fill_slice(TrackedSlice::items::<T>(header), size, f)?;
+            (*header.as_ptr()).len = size;
+            Ok(items)
+        }
+    }
+
+    pub fn alloc_slice_copy<T: Copy>(&self, src: &[T]) -> AllocResult<&mut [T]> {
+        self.alloc.alloc_slice_copy(src)
+    }
+
+    pub fn alloc_slice_clone<T: Clone + 'mem>(&self, src: &[T]) -> AllocResult<&mut [T]> {
+        self.alloc_from_fn(src.len(), |i| src[i].clone())
+    }
+
+    pub fn alloc_str(&self, src: &str) -> AllocResult<&mut str> {
+        self.alloc.alloc_str(src)
+    }
+
+    pub fn alloc_fmt(&self, args: fmt::Arguments<'_>) -> AllocResult<&mut str> {
+        self.alloc.alloc_fmt(args)
+    }
+
+    pub fn alloc_uninit<T>(&self) -> AllocResult<&mut MaybeUninit<T>> {
+        self.alloc.alloc_uninit()
+    }
+
+    pub fn alloc_uninit_slice<T>(&self, size: usize) -> AllocResult<&mut [MaybeUninit<T>]> {
+        self.alloc.alloc_uninit_slice(size)
+    }
+
+    pub fn alloc_layout(&self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+        self.alloc.alloc_layout(layout)
+    }
+
+    pub fn scope<R>(&mut self, f: impl FnOnce(&mut Alloc<'_>) -> R) -> R {
+        self.alloc.scope(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Heap;
+    use crate::OutOfMemory;
+    use std::string::String;
+    use std::vec;
+    use std::vec::Vec;
+
+    #[test]
+    fn reset_reuses_whole_heap() {
+        let mut heap = Heap([0u8; 8]);
+        let mut alloc = ResettableAlloc::new(&mut heap.0);
+        let first = alloc.alloc_slice_copy(&[1u8; 8]).unwrap().as_ptr();
+        assert_eq!(alloc.alloc(0u8), Err(OutOfMemory));
+        alloc.reset();
+        let a = alloc.alloc(2u32).unwrap();
+        let b = alloc.alloc(3u32).unwrap();
+        assert_eq!(a as *mut u32 as *const u8, first);
+        assert_eq!((*a, *b), (2, 3));
+        alloc.reset();
+        assert_eq!(alloc.alloc_str("reused!!").unwrap(), "reused!!");
+    }
+
+    struct Guard<'a> {
+        id: u8,
+        log: &'a core::cell::RefCell<Vec<u8>>,
+    }
+
+    impl Drop for Guard<'_> {
+        fn drop(&mut self) {
+            self.log.borrow_mut().push(self.id);
+        }
+    }
+
+    #[test]
+    fn drop_runs_in_reverse_order() {
+        let log = core::cell::RefCell::new(Vec::new());
+        let mut heap: [u8; 256] = core::array::from_fn(|_| 0);
+        let alloc = ResettableAlloc::new(&mut heap);
+        for id in 0..4 {
+            alloc.alloc(Guard { id, log: &log }).unwrap();
+        }
+        alloc.alloc_no_drop(Guard { id: 9, log: &log }).unwrap();
+        assert!(log.borrow().is_empty());
+        drop(alloc);
+        assert_eq!(*log.borrow(), [3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn reset_and_rewind_drop_values() {
+        let log = core::cell::RefCell::new(Vec::new());
+        let mut heap: [u8; 256] = core::array::from_fn(|_| 0);
+        let mut alloc = ResettableAlloc::new(&mut heap);
+        alloc.alloc(Guard { id: 0, log: &log }).unwrap();
+        let checkpoint = alloc.checkpoint();
+        alloc.alloc(Guard { id: 1, log: &log }).unwrap();
+        alloc.alloc(Guard { id: 2, log: &log }).unwrap();
+        alloc.rewind(checkpoint);
+        assert_eq!(*log.borrow(), [2, 1]);
+        alloc.alloc(Guard { id: 3, log: &log }).unwrap();
+        alloc.reset();
+        assert_eq!(*log.borrow(), [2, 1, 3, 0]);
+        drop(alloc);
+        assert_eq!(log.borrow().len(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "checkpoint is past the current cursor")]
+    fn rewind_to_foreign_checkpoint_panics() {
+        let mut large_heap = Heap([0u8; 256]);
+        let large = ResettableAlloc::new(&mut large_heap.0);
+        large.alloc([0u8; 200]).unwrap();
+        let checkpoint = large.checkpoint();
+
+        let mut small_heap = Heap([0u8; 8]);
+        let mut small = ResettableAlloc::new(&mut small_heap.0);
+        small.rewind(checkpoint);
+    }
+
+    #[test]
+    #[should_panic(expected = "checkpoint is past the current cursor")]
+    fn rewind_to_stale_checkpoint_panics() {
+        let mut heap = Heap([0u8; 64]);
+        let mut alloc = ResettableAlloc::new(&mut heap.0);
+        let start = alloc.checkpoint();
+        alloc.alloc(1u64).unwrap();
+        let stale = alloc.checkpoint();
+        alloc.rewind(start);
+        alloc.rewind(stale);
+    }
+
+    #[test]
+    fn values_without_drop_glue_take_no_space() {
+        let mut heap = Heap([0u8; 8]);
+        let alloc = ResettableAlloc::new(&mut heap.0);
+        assert_eq!(*alloc.alloc(7u64).unwrap(), 7);
+        assert_eq!(alloc.alloc(0u8), Err(OutOfMemory));
+    }
+
+    #[test]
+    fn slices_are_dropped() {
+        let log = core::cell::RefCell::new(Vec::new());
+        let mut heap = Heap([0u8; 256]);
+        let mut alloc = ResettableAlloc::new(&mut heap.0);
+        alloc.alloc(Guard { id: 0, log: &log }).unwrap();
+        let checkpoint = alloc.checkpoint();
+        let guards = alloc.alloc_from_fn(3, |i| Guard { id: i as u8 + 1, log: &log }).unwrap();
+        assert_eq!(guards[2].id, 3);
+        alloc.alloc(Guard { id: 4, log: &log }).unwrap();
+        alloc.rewind(checkpoint);
+        assert_eq!(*log.borrow(), [4, 1, 2, 3]);
+        drop(alloc);
+        assert_eq!(*log.borrow(), [4, 1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn failed_slice_drops_its_prefix_once() {
+        let log = core::cell::RefCell::new(Vec::new());
+        let mut heap = Heap([0u8; 256]);
+        let mut alloc = ResettableAlloc::new(&mut heap.0);
+        let result = alloc.try_alloc_from_fn(4, |i| if i < 2 { Ok(Guard { id: i as u8, log: &log }) } else { Err(OutOfMemory) });
+        assert!(result.is_err());
+        assert_eq!(*log.borrow(), [0, 1]);
+        alloc.reset();
+        assert_eq!(*log.borrow(), [0, 1]);
+    }
+
+    #[test]
+    fn strings_are_freed() {
+        let mut heap: [u8; 256] = core::array::from_fn(|_| 0);
+        let alloc = ResettableAlloc::new(&mut heap);
+        let s = alloc.alloc(String::from("owned")).unwrap();
+        s.push_str(" string");
+        let v = alloc.alloc(vec![1, 2, 3]).unwrap();
+        v.push(4);
+        let cloned = alloc.alloc_slice_clone(&[String::from("a"), String::from("b")]).unwrap();
+        cloned[1].push('c');
+        assert_eq!(cloned[1], "bc");
+        assert_eq!((s.as_str(), v.len()), ("owned string", 4));
+    }
+}
@@ -0,0 +1,119 @@
+/// Usage counters of an `Alloc`, returned by `Alloc::stats`.
+///
+/// `bytes_used` is the current cursor position; the other counters
+/// accumulate over the lifetime of the allocator and survive resets.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AllocStats {
+    pub bytes_used: usize,
+    pub padding_bytes: usize,
+    pub allocations: usize,
+    pub largest_allocation: usize,
+    pub peak_bytes_used: usize,
+}
+
+impl AllocStats {
+    pub(crate) fn record_padding(&mut self, padding: usize) {
+        self.padding_bytes += padding;
+    }
+
+    pub(crate) fn record_allocation(&mut self, size: usize) {
+        self.allocations += 1;
+        self.largest_allocation = self.largest_allocation.max(size);
+    }
+
+    // An allocation resized in place counts towards the largest size and the peak only
+    pub(crate) fn record_resize(&mut self, size: usize, bytes_used: usize) {
+        self.largest_allocation = self.largest_allocation.max(size);
+        self.record_usage(bytes_used);
+    }
+
+    pub(crate) fn record_usage(&mut self, bytes_used: usize) {
+        self.peak_bytes_used = self.peak_bytes_used.max(bytes_used);
+    }
+
+    // Folds the counters of a `scope` child that started at `base` into its parent
+    #[cfg(feature = "stats")]
+    pub(crate) fn merge_child(&mut self, base: usize, child: AllocStats) {
+        self.padding_bytes += child.padding_bytes;
+        self.allocations += child.allocations;
+        self.largest_allocation = self.largest_allocation.max(child.largest_allocation);
+        self.record_usage(base + child.peak_bytes_used);
+    }
+}
+
+#[cfg(all(test, feature = "stats"))]
+mod tests {
+    use super::*;
+    use crate::test_util::Heap;
+    use crate::Alloc;
+
+    #[test]
+    fn counts_allocations_and_padding() {
+        let mut heap = Heap([0; 64]);
+        let alloc = Alloc::new(&mut heap.0);
+        alloc.alloc(1u8).unwrap();
+        alloc.alloc(2u64).unwrap();
+        alloc.alloc_slice_copy(&[3u16; 5]).unwrap();
+        alloc.alloc_fmt(format_args!("{}", 1234)).unwrap();
+        assert_eq!(alloc.stats(), AllocStats {
+            bytes_used: 1 + 7 + 8 + 10 + 4,
+            padding_bytes: 7,
+            allocations: 4,
+            largest_allocation: 10,
+            peak_bytes_used: 30,
+        });
+        assert_eq!(alloc.wasted_alignment_bytes(), 7);
+    }
+
+    #[test]
+    fn peak_survives_reset() {
+        let mut heap = Heap([0; 64]);
+        let mut alloc = Alloc::new(&mut heap.0);
+        alloc.alloc([0u8; 40]).unwrap();
+        unsafe { alloc.reset() };
+        alloc.alloc([0u8; 10]).unwrap();
+        let stats = alloc.stats();
+        assert_eq!((stats.bytes_used, stats.peak_bytes_used, stats.allocations), (10, 40, 2));
+    }
+
+    #[test]
+    fn scope_is_folded_into_parent() {
+        let mut heap = Heap([0; 64]);
+        let mut alloc = Alloc::new(&mut heap.0);
+        alloc.alloc(0u8).unwrap();
+        alloc.scope(|child| {
+            child.alloc(0u32).unwrap();
+            child.alloc([0u8; 20]).unwrap();
+        });
+        assert_eq!(alloc.stats(), AllocStats {
+            bytes_used: 1,
+            padding_bytes: 3,
+            allocations: 3,
+            largest_allocation: 20,
+            peak_bytes_used: 28,
+        });
+    }
+
+    #[test]
+    fn in_place_growth_counts_peak() {
+        let mut heap = Heap([0; 64]);
+        let alloc = Alloc::new(&mut heap.0);
+        let mut v = crate::ArenaVec::new_in(&alloc);
+        for i in 0..32u8 {
+            v.push(i).unwrap();
+        }
+        let stats = alloc.stats();
+        assert_eq!((stats.allocations, stats.largest_allocation), (1, 32));
+        assert_eq!(stats.peak_bytes_used, alloc.used());
+    }
+
+    #[test]
+    fn failed_alloc_consumes_no_padding() {
+        let mut heap = Heap([0; 64]);
+        let alloc = Alloc::new(&mut heap.0);
+        alloc.alloc([0u8; 57]).unwrap();
+        assert!(alloc.alloc(2u64).is_err());
+        let stats = alloc.stats();
+        assert_eq!((stats.bytes_used, stats.padding_bytes, stats.allocations), (57, 0, 1));
+    }
+}
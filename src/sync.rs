@@ -0,0 +1,192 @@
+use core::alloc::Layout;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{align_padding, detach, AllocResult, ArenaAllocator, OutOfMemory};
+
+/// Lock-free bump allocator that can be shared between threads.
+/// The cursor is bumped with compare-exchange, alignment padding included.
+pub struct SyncAlloc<'mem> {
+    heap: *mut u8,
+    size: usize,
+    offset: AtomicUsize,
+    _mem: PhantomData<&'mem mut [u8]>,
+}
+
+unsafe impl Send for SyncAlloc<'_> {}
+unsafe impl Sync for SyncAlloc<'_> {}
+
+impl<'mem> SyncAlloc<'mem> {
+    pub fn new(heap: &'mem mut [u8]) -> Self {
+        SyncAlloc { heap: heap.as_mut_ptr(), size: heap.len(), offset: AtomicUsize::new(0), _mem: PhantomData }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.size
+    }
+
+    pub fn used(&self) -> usize {
+        self.offset.load(Ordering::Relaxed)
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.size - self.used()
+    }
+
+    pub fn alloc<'item, T>(&self, item: T) -> AllocResult<&'item mut T>
+        where 'mem: 'item
+    {
+        Ok(unsafe { detach(ArenaAllocator::alloc(self, item)?) })
+    }
+
+    pub fn alloc_from_fn<'item, T>(&self, size: usize, f: impl FnMut(usize) -> T) -> AllocResult<&'item mut [T]>
+        where 'mem: 'item
+    {
+        Ok(unsafe { detach(ArenaAllocator::alloc_from_fn(self, size, f)?) })
+    }
+
+    pub fn try_alloc_from_fn<'item, T, E>(&self, size: usize, f: impl FnMut(usize) -> Result<T, E>) -> Result<&'item mut [T], E>
+        where 'mem: 'item, E: From<OutOfMemory>
+    {
+        Ok(unsafe { detach(ArenaAllocator::try_alloc_from_fn(self, size, f)?) })
+    }
+
+    pub fn alloc_slice_copy<'item, T: Copy>(&self, src: &[T]) -> AllocResult<&'item mut [T]>
+        where 'mem: 'item
+    {
+        Ok(unsafe { detach(ArenaAllocator::alloc_slice_copy(self, src)?) })
+    }
+
+    pub fn alloc_str<'item>(&self, src: &str) -> AllocResult<&'item mut str>
+        where 'mem: 'item
+    {
+        Ok(unsafe { detach(ArenaAllocator::alloc_str(self, src)?) })
+    }
+
+    pub fn alloc_layout(&self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+        let ptr = self.reserve(layout)?;
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    fn reserve(&self, layout: Layout) -> AllocResult<NonNull<u8>> {
+        let mut offset = self.offset.load(Ordering::Relaxed);
+        loop {
+            let waste_bytes = align_padding(self.heap as usize + offset, layout.align());
+            let start = offset + waste_bytes;
+            let new_offset = match start.checked_add(layout.size()) {
+                Some(new_offset) if new_offset <= self.size => new_offset,
+                _ => return Err(OutOfMemory),
+            };
+            match self.offset.compare_exchange_weak(offset, new_offset, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => return Ok(unsafe { NonNull::new_unchecked(self.heap.add(start)) }),
+                Err(current) => offset = current,
+            }
+        }
+    }
+}
+
+unsafe impl ArenaAllocator for SyncAlloc<'_> {
+    fn alloc_layout(&self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+        SyncAlloc::alloc_layout(self, layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Heap;
+    use std::vec;
+    use std::vec::Vec;
+
+    #[test]
+    fn alloc_aligned() {
+        let mut heap: [u8; 64] = core::array::from_fn(|_| 0);
+        let alloc = SyncAlloc::new(&mut heap);
+        let byte = alloc.alloc(1u8).unwrap();
+        let word = alloc.alloc(2u64).unwrap();
+        let slice = alloc.alloc_from_fn(3, |i| i as u16).unwrap();
+        assert!((word as *mut u64 as usize).is_multiple_of(8));
+        assert_eq!((*byte, *word, &*slice), (1, 2, &[0, 1, 2][..]));
+        assert_eq!(alloc.alloc([0u8; 64]), Err(OutOfMemory));
+    }
+
+    #[test]
+    fn threads_never_overlap() {
+        const THREADS: usize = 8;
+        const PER_THREAD: usize = 500;
+
+        let mut heap = vec![0u8; THREADS * PER_THREAD * 32];
+        let alloc = SyncAlloc::new(&mut heap);
+        let mut ranges: Vec<(usize, usize)> = std::thread::scope(|s| {
+            let workers: Vec<_> = (0..THREADS)
+                .map(|t| {
+                    let alloc = &alloc;
+                    s.spawn(move || {
+                        let mut ranges = Vec::new();
+                        for i in 0..PER_THREAD {
+                            let (addr, size) = match i % 3 {
+                                0 => (alloc.alloc(t as u8).unwrap() as *mut u8 as usize, 1),
+                                1 => {
+                                    let value = alloc.alloc((t * PER_THREAD + i) as u64).unwrap();
+                                    assert!((value as *mut u64 as usize).is_multiple_of(8));
+                                    (value as *mut u64 as usize, 8)
+                                }
+                                _ => {
+                                    let slice = alloc.alloc_from_fn(3, |_| t as u32).unwrap();
+                                    assert!(slice.iter().all(|&x| x == t as u32));
+                                    (slice.as_ptr() as usize, 12)
+                                }
+                            };
+                            ranges.push((addr, size));
+                        }
+                        ranges
+                    })
+                })
+                .collect();
+            workers.into_iter().flat_map(|w| w.join().unwrap()).collect()
+        });
+
+        ranges.sort_unstable();
+        assert_eq!(ranges.len(), THREADS * PER_THREAD);
+        for pair in ranges.windows(2) {
+            assert!(pair[0].0 + pair[0].1 <= pair[1].0, "overlapping allocations");
+        }
+    }
+
+    #[test]
+    fn threads_exhaust_heap_exactly() {
+        let mut heap = Heap([0u8; 4096]);
+        let alloc = SyncAlloc::new(&mut heap.0);
+        let total: usize = std::thread::scope(|s| {
+            let workers: Vec<_> = (0..4)
+                .map(|_| s.spawn(|| core::iter::from_fn(|| alloc.alloc(0u32).ok()).count()))
+                .collect();
+            workers.into_iter().map(|w| w.join().unwrap()).sum()
+        });
+        assert_eq!(total, 4096 / 4);
+        assert_eq!((alloc.capacity(), alloc.used(), alloc.remaining()), (4096, 4096, 0));
+    }
+
+    #[test]
+    fn allocations_outlive_the_allocator() {
+        let mut heap = Heap([0u8; 64]);
+        let (word, name) = {
+            let alloc = SyncAlloc::new(&mut heap.0);
+            (alloc.alloc(7u64).unwrap(), alloc.alloc_str("sync").unwrap())
+        };
+        *word += 1;
+        assert_eq!((*word, &*name), (8, "sync"));
+    }
+
+    #[test]
+    fn usage_counters() {
+        let mut heap = Heap([0u8; 32]);
+        let alloc = SyncAlloc::new(&mut heap.0);
+        alloc.alloc(1u8).unwrap();
+        alloc.alloc(2u32).unwrap();
+        assert_eq!((alloc.capacity(), alloc.used(), alloc.remaining()), (32, 8, 24));
+        assert_eq!(alloc.alloc([0u8; 25]), Err(OutOfMemory));
+        assert_eq!(alloc.remaining(), 24);
+    }
+}
@@ -0,0 +1,144 @@
+use std::boxed::Box;
+use std::cell::RefCell;
+use std::vec;
+
+use crate::Alloc;
+
+pub const THREAD_ARENA_SIZE: usize = 64 * 1024;
+
+/// Usage of the current thread's arena. The high-water mark needs the `stats` feature.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadArenaStats {
+    pub capacity: usize,
+    #[cfg(feature = "stats")]
+    pub high_water_mark: usize,
+    pub scopes: usize,
+}
+
+struct ThreadArena {
+    alloc: Alloc<'static>,
+    heap: *mut [u8],
+    stats: ThreadArenaStats,
+}
+
+impl ThreadArena {
+    fn new(size: usize) -> Self {
+        let heap = Box::into_raw(vec![0u8; size].into_boxed_slice());
+        ThreadArena {
+            alloc: Alloc::new(unsafe { &mut *heap }),
+            heap,
+            stats: ThreadArenaStats { capacity: size, ..ThreadArenaStats::default() },
+        }
+    }
+}
+
+impl Drop for ThreadArena {
+    fn drop(&mut self) {
+        drop(unsafe { Box::from_raw(self.heap) });
+    }
+}
+
+std::thread_local! {
+    static THREAD_ARENA: RefCell<Option<ThreadArena>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` with this thread's arena, created with `THREAD_ARENA_SIZE` bytes
+/// on first use. Everything `f` allocates is released when it returns.
+///
+/// Panics when called from inside another `with_thread_arena` closure.
+pub fn with_thread_arena<R>(f: impl FnOnce(&mut Alloc<'_>) -> R) -> R {
+    THREAD_ARENA.with(|arena| {
+        let mut arena = arena.try_borrow_mut().expect("thread arena is already in use");
+        let arena = arena.get_or_insert_with(|| ThreadArena::new(THREAD_ARENA_SIZE));
+
+        let result = arena.alloc.scope(f);
+
+        arena.stats.scopes += 1;
+        // Each scope starts at offset 0, so the arena's peak is the most any scope ever held
+        #[cfg(feature = "stats")]
+        {
+            arena.stats.high_water_mark = arena.alloc.stats().peak_bytes_used;
+        }
+        result
+    })
+}
+
+/// Stats of this thread's arena, all zero if it was never used.
+pub fn thread_arena_stats() -> ThreadArenaStats {
+    THREAD_ARENA.with(|arena| arena.borrow().as_ref().map(|arena| arena.stats).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "stats")]
+    use crate::ArenaVec;
+
+    #[test]
+    fn lazily_created_per_thread() {
+        std::thread::spawn(|| {
+            assert_eq!(thread_arena_stats(), ThreadArenaStats::default());
+            let sum = with_thread_arena(|a| a.alloc_from_fn(10, |i| i as u32).unwrap().iter().sum::<u32>());
+            assert_eq!(sum, 45);
+            let stats = thread_arena_stats();
+            assert_eq!(stats.capacity, THREAD_ARENA_SIZE);
+            assert_eq!(stats.scopes, 1);
+            #[cfg(feature = "stats")]
+            assert!(stats.high_water_mark >= 40);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn memory_is_reused_between_scopes() {
+        std::thread::spawn(|| {
+            let first = with_thread_arena(|a| a.alloc([1u8; 1024]).unwrap().as_ptr() as usize);
+            let second = with_thread_arena(|a| a.alloc([2u8; 1024]).unwrap().as_ptr() as usize);
+            assert_eq!(first, second);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "stats")]
+    fn high_water_mark_per_thread() {
+        let marks: std::vec::Vec<usize> = (1..=4)
+            .map(|n| {
+                std::thread::spawn(move || {
+                    with_thread_arena(|a| {
+                        let alloc = &*a;
+                        let mut v = ArenaVec::new_in(alloc);
+                        v.try_extend(core::iter::repeat_n(0u8, n * 1000)).unwrap();
+                    });
+                    with_thread_arena(|a| a.alloc(0u8).map(|_| ()).unwrap());
+                    thread_arena_stats().high_water_mark
+                })
+            })
+            .map(|handle| handle.join().unwrap())
+            .collect();
+        assert!(marks.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(marks[0] >= 1000);
+    }
+
+    #[test]
+    #[cfg(feature = "stats")]
+    fn high_water_mark_is_peak_not_exit() {
+        std::thread::spawn(|| {
+            with_thread_arena(|a| {
+                a.scope(|inner| inner.alloc([0u8; 4000]).map(|_| ()).unwrap());
+                assert_eq!(a.used(), 0);
+            });
+            assert_eq!(thread_arena_stats().high_water_mark, 4000);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "thread arena is already in use")]
+    fn nested_use_panics() {
+        with_thread_arena(|_| with_thread_arena(|_| ()));
+    }
+}
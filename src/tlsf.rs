@@ -0,0 +1,533 @@
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+use crate::{align_padding, AllocResult, ArenaAllocator, OutOfMemory};
+
+// Block layout: [prev_phys, size | FREE] header, then the payload. Free blocks
+// keep their free list links in the first two payload words. List heads live
+// at the start of the heap, one per (first level, second level) size class
+const WORD: usize = core::mem::size_of::<usize>();
+const HEADER: usize = 2 * WORD;
+const MIN_BLOCK: usize = 4 * WORD;
+const FREE: usize = 1;
+const NIL: usize = usize::MAX;
+const SL_BITS: u32 = 4;
+const SL_COUNT: usize = 1 << SL_BITS;
+const FL_SHIFT: u32 = MIN_BLOCK.trailing_zeros();
+const FL_MAX: usize = usize::BITS as usize;
+
+/// Two-level segregated fit allocator: `alloc_layout`, `dealloc` and
+/// `realloc` run in constant time, using bitmaps to find a free block of
+/// the right size class and merging freed blocks with their neighbours.
+pub struct TlsfAlloc<'mem> {
+    heads: *mut usize,
+    blocks: *mut u8,
+    size: usize,
+    fl_count: usize,
+    fl_bitmap: Cell<usize>,
+    sl_bitmaps: [Cell<u16>; FL_MAX],
+    #[cfg(test)]
+    steps: Cell<usize>,
+    _mem: PhantomData<&'mem mut [u8]>,
+}
+
+unsafe impl Send for TlsfAlloc<'_> {}
+
+impl<'mem> TlsfAlloc<'mem> {
+    pub fn new(heap: &'mem mut [u8]) -> Self {
+        let padding = align_padding(heap.as_ptr() as usize, WORD).min(heap.len());
+        let total = (heap.len() - padding) / WORD * WORD;
+        let mut fl_count = total.checked_ilog2().map_or(0, |log| log.saturating_sub(FL_SHIFT) as usize + 1);
+        let mut heads_size = fl_count * SL_COUNT * WORD;
+        if total < heads_size + MIN_BLOCK {
+            (fl_count, heads_size) = (0, 0);
+        }
+
+        let heads = unsafe { heap.as_mut_ptr().add(padding) };
+        let alloc = TlsfAlloc {
+            heads: heads.cast(),
+            blocks: unsafe { heads.add(heads_size) },
+            size: if fl_count == 0 { 0 } else { total - heads_size },
+            fl_count,
+            fl_bitmap: Cell::new(0),
+            sl_bitmaps: core::array::from_fn(|_| Cell::new(0)),
+            #[cfg(test)]
+            steps: Cell::new(0),
+            _mem: PhantomData,
+        };
+        for index in 0..fl_count * SL_COUNT {
+            unsafe { alloc.heads.add(index).write(NIL) };
+        }
+        if alloc.size > 0 {
+            alloc.set_word(0, NIL);
+            alloc.insert_free(0, alloc.size);
+        }
+        alloc
+    }
+
+    /// Bytes available for blocks, after the size class table.
+    pub fn capacity(&self) -> usize {
+        self.size
+    }
+
+    pub fn alloc_layout(&self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+        let needed = block_size(layout.size()).ok_or(OutOfMemory)?;
+        let search = if layout.align() > WORD {
+            needed.checked_add(layout.align() + MIN_BLOCK).ok_or(OutOfMemory)?
+        } else {
+            needed
+        };
+        let mut block = self.find_free(search).ok_or(OutOfMemory)?;
+        let mut size = self.size_of(block);
+        self.remove_free(block, size);
+
+        if layout.align() > WORD {
+            // Leaves the space in front of the aligned payload as a free block
+            let mut payload = block + HEADER;
+            payload += align_padding(self.blocks as usize + payload, layout.align());
+            if payload - HEADER != block && payload - HEADER - block < MIN_BLOCK {
+                payload = block + HEADER + MIN_BLOCK;
+                payload += align_padding(self.blocks as usize + payload, layout.align());
+            }
+            let gap = payload - HEADER - block;
+            if gap > 0 {
+                self.set_word(block + gap, block);
+                self.insert_free(block, gap);
+                block += gap;
+                size -= gap;
+            }
+        }
+
+        self.split(block, size, needed);
+        let ptr = unsafe { NonNull::new_unchecked(self.blocks.add(block + HEADER)) };
+        Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+    }
+
+    /// Frees the block at `ptr`, merging it with free physical neighbours.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must come from this allocator, not be freed yet, and nothing
+    /// may use the allocation afterwards.
+    pub unsafe fn dealloc(&self, ptr: NonNull<u8>, layout: Layout) {
+        let mut block = self.block_of(ptr);
+        let mut size = self.size_of(block);
+        debug_assert!(!self.is_free(block), "double free");
+        debug_assert!(size - HEADER >= layout.size(), "layout does not match the allocation");
+
+        let next = block + size;
+        if next < self.size && self.is_free(next) {
+            let next_size = self.size_of(next);
+            self.remove_free(next, next_size);
+            size += next_size;
+        }
+        let prev = self.word(block);
+        if prev != NIL && self.is_free(prev) {
+            let prev_size = self.size_of(prev);
+            self.remove_free(prev, prev_size);
+            size += prev_size;
+            block = prev;
+        }
+        self.insert_free(block, size);
+    }
+
+    /// Resizes the allocation at `ptr`, in place when the block or its free
+    /// successor has room, otherwise by moving it. On error the old
+    /// allocation is left untouched.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be a live allocation of this allocator made with `old_layout`.
+    /// If the allocation moves, the old pointer is freed.
+    pub unsafe fn realloc(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> AllocResult<NonNull<u8>> {
+        let block = self.block_of(ptr);
+        let aligned = (ptr.as_ptr() as usize).is_multiple_of(new_layout.align());
+        if let (true, Some(needed)) = (aligned, block_size(new_layout.size())) {
+            let mut size = self.size_of(block);
+            let next = block + size;
+            if needed > size && next < self.size && self.is_free(next) && size + self.size_of(next) >= needed {
+                let next_size = self.size_of(next);
+                self.remove_free(next, next_size);
+                size += next_size;
+            }
+            if needed <= size {
+                self.split(block, size, needed);
+                return Ok(ptr);
+            }
+        }
+
+        let new_ptr = self.alloc_layout(new_layout)?.cast::<u8>();
+        let copy_size = old_layout.size().min(new_layout.size());
+        unsafe {
+            core::ptr::copy_nonoverlapping(ptr.as_ptr(), new_ptr.as_ptr(), copy_size);
+            self.dealloc(ptr, old_layout);
+        }
+        Ok(new_ptr)
+    }
+
+    /// Checks the heap structure: blocks tile the heap and link back to their
+    /// neighbours, free neighbours are merged, and the size class lists and
+    /// bitmaps hold exactly the free blocks.
+    pub fn validate(&self) -> Result<(), &'static str> {
+        let mut block = 0;
+        let mut prev = NIL;
+        let mut free_count = 0;
+        while block < self.size {
+            let size = self.size_of(block);
+            if size < MIN_BLOCK || !size.is_multiple_of(WORD) || size > self.size - block {
+                return Err("corrupted block size");
+            }
+            if self.word(block) != prev { return Err("block does not link to its physical predecessor"); }
+            if self.is_free(block) {
+                if prev != NIL && self.is_free(prev) { return Err("adjacent free blocks were not merged"); }
+                free_count += 1;
+            }
+            prev = block;
+            block += size;
+        }
+
+        let mut listed = 0;
+        for fl in 0..self.fl_count {
+            if (self.fl_bitmap.get() >> fl & 1 == 1) != (self.sl_bitmaps[fl].get() != 0) {
+                return Err("first level bitmap does not match the second level");
+            }
+            for sl in 0..SL_COUNT {
+                let mut block = self.head(fl, sl);
+                if (self.sl_bitmaps[fl].get() >> sl & 1 == 1) != (block != NIL) {
+                    return Err("second level bitmap does not match the free lists");
+                }
+                let mut prev = NIL;
+                while block != NIL {
+                    if block >= self.size || !self.is_free(block) { return Err("free list entry is not a free block"); }
+                    if mapping(self.size_of(block)) != (fl, sl) { return Err("free block is in the wrong size class"); }
+                    if self.word(block + 3 * WORD) != prev { return Err("free list back link is broken"); }
+                    listed += 1;
+                    if listed > free_count { return Err("free lists have more entries than free blocks"); }
+                    prev = block;
+                    block = self.word(block + HEADER);
+                }
+            }
+        }
+        if listed != free_count { return Err("free block missing from the free lists"); }
+        Ok(())
+    }
+
+    // Marks `block` used with `needed` bytes, returning the rest as a free block
+    fn split(&self, block: usize, size: usize, needed: usize) {
+        if size - needed < MIN_BLOCK {
+            self.set_size(block, size, false);
+            self.link_next(block, size);
+            return;
+        }
+
+        self.set_size(block, needed, false);
+        let rest = block + needed;
+        let mut rest_size = size - needed;
+        let next = block + size;
+        if next < self.size && self.is_free(next) {
+            let next_size = self.size_of(next);
+            self.remove_free(next, next_size);
+            rest_size += next_size;
+        }
+        self.set_word(rest, block);
+        self.insert_free(rest, rest_size);
+    }
+
+    // First block of a size class that guarantees `size` bytes, via the bitmaps
+    fn find_free(&self, size: usize) -> Option<usize> {
+        let round = (1 << (size.ilog2() - SL_BITS)) - 1;
+        let (mut fl, sl) = mapping(size.checked_add(round)?);
+        if fl >= self.fl_count { return None; }
+
+        self.step();
+        let mut sl_map = self.sl_bitmaps[fl].get() & (u16::MAX << sl);
+        if sl_map == 0 {
+            self.step();
+            let fl_map = self.fl_bitmap.get() & usize::MAX.checked_shl(fl as u32 + 1).unwrap_or(0);
+            if fl_map == 0 { return None; }
+            fl = fl_map.trailing_zeros() as usize;
+            self.step();
+            sl_map = self.sl_bitmaps[fl].get();
+        }
+        Some(self.head(fl, sl_map.trailing_zeros() as usize))
+    }
+
+    fn insert_free(&self, block: usize, size: usize) {
+        let (fl, sl) = mapping(size);
+        let head = self.head(fl, sl);
+        self.set_size(block, size, true);
+        self.link_next(block, size);
+        self.set_word(block + HEADER, head);
+        self.set_word(block + 3 * WORD, NIL);
+        if head != NIL {
+            self.set_word(head + 3 * WORD, block);
+        }
+        self.set_head(fl, sl, block);
+        self.sl_bitmaps[fl].set(self.sl_bitmaps[fl].get() | 1 << sl);
+        self.fl_bitmap.set(self.fl_bitmap.get() | 1 << fl);
+    }
+
+    fn remove_free(&self, block: usize, size: usize) {
+        let (fl, sl) = mapping(size);
+        let next = self.word(block + HEADER);
+        let prev = self.word(block + 3 * WORD);
+        if next != NIL {
+            self.set_word(next + 3 * WORD, prev);
+        }
+        if prev != NIL {
+            self.set_word(prev + HEADER, next);
+            return;
+        }
+
+        self.set_head(fl, sl, next);
+        if next == NIL {
+            self.sl_bitmaps[fl].set(self.sl_bitmaps[fl].get() & !(1 << sl));
+            if self.sl_bitmaps[fl].get() == 0 {
+                self.fl_bitmap.set(self.fl_bitmap.get() & !(1 << fl));
+            }
+        }
+    }
+
+    // Points the block after `block` back at it
+    fn link_next(&self, block: usize, size: usize) {
+        if block + size < self.size {
+            self.set_word(block + size, block);
+        }
+    }
+
+    fn block_of(&self, ptr: NonNull<u8>) -> usize {
+        ptr.as_ptr() as usize - self.blocks as usize - HEADER
+    }
+
+    fn is_free(&self, block: usize) -> bool {
+        self.word(block + WORD) & FREE != 0
+    }
+
+    fn size_of(&self, block: usize) -> usize {
+        self.word(block + WORD) & !FREE
+    }
+
+    fn set_size(&self, block: usize, size: usize, free: bool) {
+        self.set_word(block + WORD, size | if free { FREE } else { 0 });
+    }
+
+    fn head(&self, fl: usize, sl: usize) -> usize {
+        self.step();
+        unsafe { self.heads.add(fl * SL_COUNT + sl).read() }
+    }
+
+    fn set_head(&self, fl: usize, sl: usize, block: usize) {
+        self.step();
+        unsafe { self.heads.add(fl * SL_COUNT + sl).write(block) }
+    }
+
+    fn word(&self, offset: usize) -> usize {
+        self.step();
+        unsafe { self.blocks.add(offset).cast::<usize>().read() }
+    }
+
+    fn set_word(&self, offset: usize, value: usize) {
+        self.step();
+        unsafe { self.blocks.add(offset).cast::<usize>().write(value) }
+    }
+
+    // Counts heap word accesses and bitmap scans for the complexity tests
+    #[inline(always)]
+    fn step(&self) {
+        #[cfg(test)]
+        self.steps.set(self.steps.get() + 1);
+    }
+}
+
+// Block size holding `size` bytes of payload, in whole words and never below `MIN_BLOCK`
+fn block_size(size: usize) -> Option<usize> {
+    let payload = size.max(MIN_BLOCK - HEADER).checked_add(WORD - 1)? / WORD * WORD;
+    payload.checked_add(HEADER)
+}
+
+// Size class of a free block: the first level is the highest set bit and the
+// second level splits that power-of-two range into `SL_COUNT` equal parts
+fn mapping(size: usize) -> (usize, usize) {
+    let fl = size.ilog2();
+    let sl = (size >> (fl - SL_BITS)) - SL_COUNT;
+    ((fl - FL_SHIFT) as usize, sl)
+}
+
+unsafe impl ArenaAllocator for TlsfAlloc<'_> {
+    fn alloc_layout(&self, layout: Layout) -> AllocResult<NonNull<[u8]>> {
+        TlsfAlloc::alloc_layout(self, layout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{Heap, Rng};
+    use std::vec;
+    use std::vec::Vec;
+
+    // Heap word accesses and bitmap scans a single call may take: a search,
+    // the neighbour merges and the reinsertion of a split remainder or an
+    // alignment gap. The same bound holds for every heap size
+    const MAX_STEPS: usize = 32;
+
+    fn addr(ptr: NonNull<[u8]>) -> usize {
+        ptr.cast::<u8>().as_ptr() as usize
+    }
+
+    #[test]
+    fn size_classes() {
+        assert_eq!(mapping(MIN_BLOCK), (0, 0));
+        assert_eq!(mapping(MIN_BLOCK + MIN_BLOCK / 2), (0, SL_COUNT / 2));
+        assert_eq!(mapping(1 << 20), ((20 - FL_SHIFT) as usize, 0));
+        assert_eq!(mapping((1 << 20) + (1 << 19)), ((20 - FL_SHIFT) as usize, SL_COUNT / 2));
+    }
+
+    #[test]
+    fn alloc_and_free_merges_back() {
+        let mut heap = Heap([0u8; 4096]);
+        let alloc = TlsfAlloc::new(&mut heap.0);
+        let layout = Layout::new::<[u8; 40]>();
+        let blocks: Vec<_> = (0..5).map(|_| alloc.alloc_layout(layout).unwrap()).collect();
+        alloc.validate().unwrap();
+        for i in [1, 3, 0, 4, 2] {
+            unsafe { alloc.dealloc(blocks[i].cast(), layout) };
+            alloc.validate().unwrap();
+        }
+        assert_eq!(alloc.size_of(0), alloc.capacity());
+        assert_eq!(addr(alloc.alloc_layout(layout).unwrap()), addr(blocks[0]));
+    }
+
+    #[test]
+    fn alloc_over_aligned() {
+        let mut heap = Heap([0u8; 4096]);
+        let alloc = TlsfAlloc::new(&mut heap.0);
+        alloc.alloc_layout(Layout::new::<u8>()).unwrap();
+        for align in [16, 32, 64, 256] {
+            let ptr = alloc.alloc_layout(Layout::from_size_align(24, align).unwrap()).unwrap();
+            assert!(addr(ptr).is_multiple_of(align));
+            alloc.validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn out_of_memory() {
+        let mut heap = Heap([0u8; 1024]);
+        let alloc = TlsfAlloc::new(&mut heap.0);
+        assert_eq!(alloc.alloc_layout(Layout::new::<[u8; 1024]>()), Err(OutOfMemory));
+        assert_eq!(alloc.alloc_layout(Layout::from_size_align(isize::MAX as usize - 7, 8).unwrap()), Err(OutOfMemory));
+        let half = Layout::from_size_align(alloc.capacity() / 2, 1).unwrap();
+        assert!(alloc.alloc_layout(half).is_ok());
+        assert_eq!(alloc.alloc_layout(half), Err(OutOfMemory));
+        alloc.validate().unwrap();
+
+        let mut tiny = Heap([0u8; 16]);
+        let alloc = TlsfAlloc::new(&mut tiny.0);
+        assert_eq!(alloc.capacity(), 0);
+        assert_eq!(alloc.alloc_layout(Layout::new::<u8>()), Err(OutOfMemory));
+    }
+
+    #[test]
+    fn realloc_in_place_and_moving() {
+        let mut heap = Heap([0u8; 2048]);
+        let alloc = TlsfAlloc::new(&mut heap.0);
+        let small = Layout::new::<[u8; 16]>();
+        let large = Layout::new::<[u8; 128]>();
+        let ptr = alloc.alloc_layout(small).unwrap().cast::<u8>();
+        unsafe {
+            ptr.write_bytes(7, 16);
+            assert_eq!(alloc.realloc(ptr, small, large).unwrap(), ptr);
+            let blocker = alloc.alloc_layout(small).unwrap();
+            assert_eq!(alloc.realloc(ptr, large, small).unwrap(), ptr);
+            alloc.validate().unwrap();
+            let moved = alloc.realloc(ptr, small, Layout::new::<[u8; 256]>()).unwrap();
+            assert_ne!(moved, ptr);
+            assert_eq!(core::slice::from_raw_parts(moved.as_ptr(), 16), &[7; 16]);
+            alloc.validate().unwrap();
+            alloc.dealloc(blocker.cast(), small);
+            alloc.dealloc(moved, Layout::new::<[u8; 256]>());
+        }
+        alloc.validate().unwrap();
+        assert_eq!(alloc.size_of(0), alloc.capacity());
+    }
+
+    // Random alloc/free/realloc mix, checking contents, the heap structure and
+    // that no single call takes more than `MAX_STEPS` steps
+    fn random_workload(heap: &mut [u8], seed: u64, ops: usize) -> usize {
+        let alloc = TlsfAlloc::new(heap);
+        let mut live: Vec<(NonNull<u8>, Layout, u8)> = Vec::new();
+        let mut rng = Rng(seed);
+        let check = |ptr: NonNull<u8>, size: usize, tag: u8| unsafe {
+            assert!(core::slice::from_raw_parts(ptr.as_ptr(), size).iter().all(|&b| b == tag));
+        };
+
+        let mut max_steps = 0;
+        for step in 0..ops {
+            let tag = step as u8;
+            let align = if rng.next(4) == 0 { 64 << rng.next(7) } else { 1 << rng.next(5) };
+            let layout = Layout::from_size_align(rng.next(512), align).unwrap();
+            alloc.steps.set(0);
+            match rng.next(3) {
+                0 if !live.is_empty() => {
+                    let (ptr, old_layout, old_tag) = live.swap_remove(rng.next(live.len()));
+                    check(ptr, old_layout.size(), old_tag);
+                    unsafe { alloc.dealloc(ptr, old_layout) };
+                }
+                1 if !live.is_empty() => {
+                    let index = rng.next(live.len());
+                    let (ptr, old_layout, old_tag) = live[index];
+                    match unsafe { alloc.realloc(ptr, old_layout, layout) } {
+                        Ok(new_ptr) => {
+                            check(new_ptr, old_layout.size().min(layout.size()), old_tag);
+                            unsafe { new_ptr.write_bytes(tag, layout.size()) };
+                            live[index] = (new_ptr, layout, tag);
+                        }
+                        Err(OutOfMemory) => check(ptr, old_layout.size(), old_tag),
+                    }
+                }
+                _ => if let Ok(ptr) = alloc.alloc_layout(layout) {
+                    let ptr = ptr.cast::<u8>();
+                    assert!((ptr.as_ptr() as usize).is_multiple_of(layout.align()));
+                    unsafe { ptr.write_bytes(tag, layout.size()) };
+                    live.push((ptr, layout, tag));
+                }
+            }
+            // realloc that moves is an alloc plus a dealloc
+            max_steps = max_steps.max(alloc.steps.get());
+            assert!(alloc.steps.get() <= 2 * MAX_STEPS, "step {step} took {} steps", alloc.steps.get());
+            alloc.validate().unwrap();
+        }
+
+        for (ptr, layout, _) in live {
+            alloc.steps.set(0);
+            unsafe { alloc.dealloc(ptr, layout) };
+            assert!(alloc.steps.get() <= MAX_STEPS);
+        }
+        alloc.validate().unwrap();
+        assert_eq!(alloc.size_of(0), alloc.capacity());
+        max_steps
+    }
+
+    #[test]
+    fn random_workloads_have_bounded_steps() {
+        let ops = if cfg!(miri) { 200 } else { 3000 };
+        let mut small = Heap([0u8; 4096]);
+        let mut large = vec![0u8; 1 << 20];
+        for seed in 1..=3 {
+            let small_steps = random_workload(&mut small.0, seed, ops);
+            let large_steps = random_workload(&mut large, seed, ops);
+            assert!(small_steps <= 2 * MAX_STEPS && large_steps <= 2 * MAX_STEPS);
+        }
+    }
+
+    #[test]
+    fn typed_api() {
+        let mut heap = Heap([0u8; 1024]);
+        let alloc = TlsfAlloc::new(&mut heap.0);
+        assert_eq!(alloc.alloc_from_fn(4, |i| i as u64).unwrap(), &[0, 1, 2, 3]);
+        assert_eq!(alloc.alloc_str("tlsf").unwrap(), "tlsf");
+        alloc.validate().unwrap();
+    }
+}
@@ -0,0 +1,61 @@
+use allocator_api2::vec::Vec;
+use hashbrown::HashMap;
+use simple_allocator::Alloc;
+
+#[test]
+fn vec_new_in() {
+    let mut heap: [u8; 64] = core::array::from_fn(|_| 0);
+    let alloc = Alloc::new(&mut heap);
+    let mut v = Vec::new_in(&alloc);
+    v.extend([1u32, 2, 3, 4]);
+    assert_eq!(v.as_slice(), &[1, 2, 3, 4]);
+}
+
+#[test]
+fn vec_grows_last_in_place() {
+    let mut heap: [u8; 64] = core::array::from_fn(|_| 0);
+    let alloc = Alloc::new(&mut heap);
+    let mut v: Vec<u8, _> = Vec::with_capacity_in(4, &alloc);
+    v.extend([1, 2, 3, 4]);
+    let ptr = v.as_ptr();
+    for i in 5..=64 {
+        v.push(i);
+        assert_eq!(v.as_ptr(), ptr);
+    }
+    assert!(v.try_reserve(1).is_err());
+}
+
+#[test]
+fn vec_grows_by_copy_when_not_last() {
+    let mut heap: [u8; 128] = core::array::from_fn(|_| 0);
+    let alloc = Alloc::new(&mut heap);
+    let mut first: Vec<u32, _> = Vec::with_capacity_in(2, &alloc);
+    let mut second: Vec<u32, _> = Vec::with_capacity_in(2, &alloc);
+    first.extend([1, 2]);
+    second.extend([3, 4]);
+    let ptr = first.as_ptr();
+    first.push(5);
+    assert_ne!(first.as_ptr(), ptr);
+    assert_eq!(first.as_slice(), &[1, 2, 5]);
+    assert_eq!(second.as_slice(), &[3, 4]);
+}
+
+#[test]
+fn hash_map_new_in() {
+    let mut heap = vec![0u8; 16 * 1024];
+    let alloc = Alloc::new(&mut heap);
+    let mut map = HashMap::new_in(&alloc);
+    for i in 0..100u32 {
+        map.insert(i, i * i);
+    }
+    assert_eq!(map.len(), 100);
+    assert!((0..100).all(|i| map[&i] == i * i));
+}
+
+#[test]
+fn hash_map_out_of_memory() {
+    let mut heap: [u8; 64] = core::array::from_fn(|_| 0);
+    let alloc = Alloc::new(&mut heap);
+    let mut map: HashMap<u64, u64, _, _> = HashMap::new_in(&alloc);
+    assert!(map.try_reserve(1000).is_err());
+}
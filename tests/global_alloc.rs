@@ -0,0 +1,57 @@
+// Runs without the libtest harness so no other thread touches the global heap
+use simple_allocator::GlobalBumpAlloc;
+
+#[global_allocator]
+static GLOBAL: GlobalBumpAlloc<{ 1 << 20 }> = GlobalBumpAlloc::new();
+
+fn vec_grows() {
+    let mut v = Vec::new();
+    for i in 0..1000u32 {
+        v.push(i);
+    }
+    assert_eq!(v.len(), 1000);
+    assert!(v.iter().enumerate().all(|(i, &x)| i as u32 == x));
+}
+
+fn vec_grows_in_place_at_top() {
+    let mut v: Vec<u64> = Vec::with_capacity(1);
+    v.push(0);
+    let ptr = v.as_ptr();
+    v.reserve_exact(64);
+    assert_eq!(v.as_ptr(), ptr);
+}
+
+fn string_formats() {
+    let mut s = String::from("bump");
+    s.push_str(" allocator");
+    let formatted = format!("{s} #{}", 1);
+    assert_eq!(formatted, "bump allocator #1");
+}
+
+fn box_dealloc_rolls_back() {
+    let used = GLOBAL.used();
+    let boxed = Box::new([1u8; 32]);
+    assert_eq!(GLOBAL.used(), used + 32);
+    assert_eq!(boxed.iter().map(|&b| b as u32).sum::<u32>(), 32);
+    drop(boxed);
+    assert_eq!(GLOBAL.used(), used);
+}
+
+fn out_of_memory_is_null() {
+    let layout = std::alloc::Layout::from_size_align(GLOBAL.capacity() + 1, 1).unwrap();
+    assert!(unsafe { std::alloc::alloc(layout) }.is_null());
+}
+
+fn main() {
+    let checks: [(&str, fn()); 5] = [
+        ("vec_grows", vec_grows),
+        ("vec_grows_in_place_at_top", vec_grows_in_place_at_top),
+        ("string_formats", string_formats),
+        ("box_dealloc_rolls_back", box_dealloc_rolls_back),
+        ("out_of_memory_is_null", out_of_memory_is_null),
+    ];
+    for (name, check) in checks {
+        check();
+        println!("test {name} ... ok");
+    }
+}